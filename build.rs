@@ -3,6 +3,13 @@ use std::fs::{self, DirBuilder};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+#[cfg(feature = "validate_shaders")]
+#[path = "src/render_gl/directives.rs"]
+mod directives;
+#[cfg(feature = "validate_shaders")]
+#[path = "build/validate_shaders.rs"]
+mod validate_shaders;
+
 fn main() {
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
@@ -13,6 +20,10 @@ fn main() {
         .expect("Failed to find target dir")
         .join(env::var("PROFILE").unwrap());
 
+    // Catch shader errors now instead of as `CompileError`s at runtime
+    #[cfg(feature = "validate_shaders")]
+    validate_shaders::validate_shaders(&manifest_dir.join("assets"));
+
     copy_assets(&manifest_dir.join("assets"), &exe_path.join("assets"));
 }
 
@@ -0,0 +1,407 @@
+//! Build time shader validation, enabled by the `validate_shaders` feature.
+//!
+//! Every shader under `assets/` is preprocessed like `render_gl::preprocessor`
+//! does (`#include`s resolved, `.glsl` files split at `#pragma stage`),
+//! reading the directives with the same `render_gl::directives` helpers,
+//! parsed and validated with naga's GLSL front end, and the outputs of each
+//! vertex shader are checked against the inputs of its fragment shader.
+
+use crate::directives::{is_directive, parse_include_path, parse_stage_pragma};
+use naga::front::glsl::{Frontend, Options};
+use naga::{Binding, Module, ShaderStage, TypeInner};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// naga's front end only accepts these versions, older shaders are
+/// validated as if they declared the first one
+const NAGA_GLSL_VERSIONS: [u32; 3] = [450, 440, 460];
+
+/// One stage of a program, with every line traced back to its file
+struct StageSource {
+    stage: &'static str,
+    lines: Vec<SourceLine>,
+}
+
+#[derive(Clone)]
+struct SourceLine {
+    text: String,
+    file: String,
+    line: usize,
+}
+
+/// A vertex output or fragment input
+struct Varying {
+    name: String,
+    location: u32,
+    ty: String,
+}
+
+/// Validates the shaders in `assets_dir`, failing the build with every
+/// error found
+pub fn validate_shaders(assets_dir: &Path) {
+    // Program name -> its stages, e.g. `shaders/triangle` -> [vertex, fragment]
+    let mut programs: BTreeMap<String, Vec<StageSource>> = BTreeMap::new();
+    let mut errors: Vec<String> = Vec::new();
+
+    for entry in WalkDir::new(assets_dir).into_iter().filter_map(Result::ok) {
+        let path = entry.path();
+        let extension = match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) => extension,
+            None => continue,
+        };
+        let resource_name = resource_name(assets_dir, path);
+        let program_name = resource_name.trim_end_matches(extension).trim_end_matches('.');
+
+        let stages = match extension {
+            "vert" => load_stage(assets_dir, &resource_name, "vertex"),
+            "tesc" => load_stage(assets_dir, &resource_name, "tess_control"),
+            "tese" => load_stage(assets_dir, &resource_name, "tess_evaluation"),
+            "geom" => load_stage(assets_dir, &resource_name, "geometry"),
+            "frag" => load_stage(assets_dir, &resource_name, "fragment"),
+            "comp" => load_stage(assets_dir, &resource_name, "compute"),
+            "glsl" => load_combined(assets_dir, &resource_name),
+            _ => continue,
+        };
+
+        match stages {
+            Ok(stages) => programs
+                .entry(program_name.to_owned())
+                .or_default()
+                .extend(stages),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    for (program_name, stages) in programs.iter() {
+        let mut vertex_outputs = None;
+        let mut fragment_inputs = None;
+
+        for stage in stages.iter() {
+            let naga_stage = match stage.stage {
+                "vertex" => ShaderStage::Vertex,
+                "fragment" => ShaderStage::Fragment,
+                "compute" => ShaderStage::Compute,
+                other => {
+                    println!(
+                        "cargo:warning=Not validating {} stage of {}, naga has no {} shaders",
+                        other, program_name, other
+                    );
+                    continue;
+                }
+            };
+
+            match parse_and_validate(stage, naga_stage) {
+                Ok(module) => match naga_stage {
+                    ShaderStage::Vertex => vertex_outputs = Some(vertex_outputs_of(&module)),
+                    ShaderStage::Fragment => fragment_inputs = Some(fragment_inputs_of(&module)),
+                    _ => {}
+                },
+                Err(stage_errors) => errors.extend(stage_errors),
+            }
+        }
+
+        // Geometry and tessellation stages sit between the two and can't be checked
+        let has_middle_stage = stages
+            .iter()
+            .any(|stage| stage.stage.starts_with("tess") || stage.stage == "geometry");
+
+        if let (Some(outputs), Some(inputs)) = (vertex_outputs, fragment_inputs) {
+            if !has_middle_stage {
+                errors.extend(interface_mismatches(program_name, &outputs, &inputs));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        panic!(
+            "{} shader error(s) found in {}:\n\n{}\n",
+            errors.len(),
+            assets_dir.display(),
+            errors.join("\n\n")
+        );
+    }
+}
+
+/// `shaders/triangle.vert` for `<assets_dir>/shaders/triangle.vert`
+fn resource_name(assets_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(assets_dir)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn load_stage(
+    assets_dir: &Path,
+    resource_name: &str,
+    stage: &'static str,
+) -> Result<Vec<StageSource>, String> {
+    let mut lines = Vec::new();
+    expand(assets_dir, resource_name, &mut Vec::new(), &mut lines)?;
+    Ok(vec![StageSource { stage, lines }])
+}
+
+/// Splits a `.glsl` file at its `#pragma stage NAME` lines, the code
+/// before the first one is shared by every stage
+fn load_combined(assets_dir: &Path, resource_name: &str) -> Result<Vec<StageSource>, String> {
+    let mut lines = Vec::new();
+    expand(assets_dir, resource_name, &mut Vec::new(), &mut lines)?;
+
+    let mut shared: Vec<SourceLine> = Vec::new();
+    let mut stages: Vec<StageSource> = Vec::new();
+
+    for line in lines {
+        if let Some(stage) = parse_stage_pragma(&line.text) {
+            let stage = match stage {
+                "vertex" => "vertex",
+                "tess_control" => "tess_control",
+                "tess_evaluation" => "tess_evaluation",
+                "geometry" => "geometry",
+                "fragment" => "fragment",
+                "compute" => "compute",
+                other => {
+                    return Err(format!(
+                        "error: unknown shader stage `{}`\n --> {}:{}",
+                        other, line.file, line.line
+                    ))
+                }
+            };
+            stages.push(StageSource {
+                stage,
+                lines: shared.clone(),
+            });
+            continue;
+        }
+
+        match stages.last_mut() {
+            Some(stage) => stage.lines.push(line),
+            None => shared.push(line),
+        }
+    }
+
+    Ok(stages)
+}
+
+/// Appends the lines of `name` to `lines`, with `#include`s replaced
+/// by the lines of the included resource
+fn expand(
+    assets_dir: &Path,
+    name: &str,
+    include_stack: &mut Vec<String>,
+    lines: &mut Vec<SourceLine>,
+) -> Result<(), String> {
+    if include_stack.iter().any(|included| included == name) {
+        include_stack.push(name.to_owned());
+        return Err(format!("error: include cycle {}", include_stack.join(" -> ")));
+    }
+
+    let path = name
+        .split('/')
+        .fold(assets_dir.to_path_buf(), |path, part| path.join(part));
+    let source = fs::read_to_string(&path)
+        .map_err(|e| format!("error: failed to read {}: {}", path.display(), e))?;
+
+    include_stack.push(name.to_owned());
+
+    for (index, text) in source.lines().enumerate() {
+        if is_directive(text, "include") {
+            let included = parse_include_path(text).ok_or_else(|| {
+                format!(
+                    "error: malformed #include, expected #include \"path\"\n --> {}:{}",
+                    name,
+                    index + 1
+                )
+            })?;
+            expand(assets_dir, included, include_stack, lines)?;
+        } else {
+            lines.push(SourceLine {
+                text: text.to_owned(),
+                file: name.to_owned(),
+                line: index + 1,
+            });
+        }
+    }
+
+    include_stack.pop();
+    Ok(())
+}
+
+/// Source handed to naga, with an unsupported `#version` raised to one it accepts
+fn naga_source(stage: &StageSource) -> String {
+    stage
+        .lines
+        .iter()
+        .map(|line| {
+            let mut words = line.text.split_whitespace();
+            match (words.next(), words.next().and_then(|version| version.parse::<u32>().ok())) {
+                (Some("#version"), Some(version)) if !NAGA_GLSL_VERSIONS.contains(&version) => {
+                    let profile = words.collect::<Vec<&str>>().join(" ");
+                    format!("#version {} {}", NAGA_GLSL_VERSIONS[0], profile)
+                }
+                _ => line.text.clone(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn parse_and_validate(stage: &StageSource, naga_stage: ShaderStage) -> Result<Module, Vec<String>> {
+    let source = naga_source(stage);
+
+    let module = Frontend::default()
+        .parse(&Options::from(naga_stage), &source)
+        .map_err(|errors| {
+            errors
+                .iter()
+                .map(|error| {
+                    let line = error.meta.location(&source).line_number as usize;
+                    format_error(stage, &error.kind.to_string(), line)
+                })
+                .collect::<Vec<String>>()
+        })?;
+
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|error| {
+        let line = error
+            .location(&source)
+            .map(|location| location.line_number as usize)
+            .unwrap_or(0);
+        vec![format_error(stage, &error.as_inner().to_string(), line)]
+    })?;
+
+    Ok(module)
+}
+
+/// Formats like `render_gl::diagnostics::Diagnostic`
+fn format_error(stage: &StageSource, message: &str, line: usize) -> String {
+    match line.checked_sub(1).and_then(|index| stage.lines.get(index)) {
+        Some(source_line) => {
+            let gutter = " ".repeat(source_line.line.to_string().len());
+            format!(
+                "error: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} |",
+                message,
+                gutter,
+                source_line.file,
+                source_line.line,
+                gutter,
+                source_line.line,
+                source_line.text,
+                gutter
+            )
+        }
+        None => format!("error: {} (in {} stage)", message, stage.stage),
+    }
+}
+
+fn vertex_outputs_of(module: &Module) -> Vec<Varying> {
+    module
+        .entry_points
+        .iter()
+        .filter_map(|entry_point| entry_point.function.result.as_ref())
+        .flat_map(|result| varyings(module, None, result.ty, result.binding.as_ref()))
+        .collect()
+}
+
+fn fragment_inputs_of(module: &Module) -> Vec<Varying> {
+    module
+        .entry_points
+        .iter()
+        .flat_map(|entry_point| entry_point.function.arguments.iter())
+        .flat_map(|argument| {
+            varyings(module, argument.name.as_deref(), argument.ty, argument.binding.as_ref())
+        })
+        .collect()
+}
+
+/// User defined varyings of a value, or of the members of a struct value.
+/// Built-ins like `gl_Position` are left out.
+fn varyings(
+    module: &Module,
+    name: Option<&str>,
+    ty: naga::Handle<naga::Type>,
+    binding: Option<&Binding>,
+) -> Vec<Varying> {
+    match (binding, &module.types[ty].inner) {
+        (Some(Binding::Location { location, .. }), inner) => vec![Varying {
+            name: name.unwrap_or("<unnamed>").to_owned(),
+            location: *location,
+            ty: type_name(inner),
+        }],
+        (None, TypeInner::Struct { members, .. }) => members
+            .iter()
+            .flat_map(|member| {
+                varyings(module, member.name.as_deref(), member.ty, member.binding.as_ref())
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Fragment inputs are matched to vertex outputs by name, falling back to
+/// location for outputs with another name
+fn interface_mismatches(
+    program_name: &str,
+    outputs: &[Varying],
+    inputs: &[Varying],
+) -> Vec<String> {
+    inputs
+        .iter()
+        .filter_map(|input| {
+            let output = outputs
+                .iter()
+                .find(|output| output.name == input.name)
+                .or_else(|| outputs.iter().find(|output| output.location == input.location));
+
+            match output {
+                None => Some(format!(
+                    "error: fragment input `{}` of {} is not written by the vertex shader",
+                    input.name, program_name
+                )),
+                Some(output) if output.ty != input.ty => Some(format!(
+                    "error: fragment input `{}` of {} is `{}`, but vertex output `{}` is `{}`",
+                    input.name, program_name, input.ty, output.name, output.ty
+                )),
+                Some(_) => None,
+            }
+        })
+        .collect()
+}
+
+/// GLSL spelling of scalar, vector and matrix types
+fn type_name(inner: &TypeInner) -> String {
+    fn prefix(scalar: naga::Scalar) -> &'static str {
+        match (scalar.kind, scalar.width) {
+            (naga::ScalarKind::Float, 8) => "d",
+            (naga::ScalarKind::Float, _) => "",
+            (naga::ScalarKind::Sint, _) => "i",
+            (naga::ScalarKind::Uint, _) => "u",
+            (naga::ScalarKind::Bool, _) => "b",
+            _ => "?",
+        }
+    }
+
+    match inner {
+        TypeInner::Scalar(scalar) => match (scalar.kind, scalar.width) {
+            (naga::ScalarKind::Float, 8) => "double".into(),
+            (naga::ScalarKind::Float, _) => "float".into(),
+            (naga::ScalarKind::Sint, _) => "int".into(),
+            (naga::ScalarKind::Uint, _) => "uint".into(),
+            (naga::ScalarKind::Bool, _) => "bool".into(),
+            _ => format!("{:?}", scalar),
+        },
+        TypeInner::Vector { size, scalar } => format!("{}vec{}", prefix(*scalar), *size as u8),
+        TypeInner::Matrix {
+            columns,
+            rows,
+            scalar,
+        } => format!("{}mat{}x{}", prefix(*scalar), *columns as u8, *rows as u8),
+        other => format!("{:?}", other),
+    }
+}
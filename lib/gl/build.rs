@@ -10,8 +10,9 @@ fn main() {
     let mut file_gl = File::create(&Path::new(&out_dir).join("bindings.rs"))
         .unwrap();
     
-    let registry = Registry::new(Api::Gl, (4, 5), Profile::Core, Fallbacks::All, [
+    let registry = Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::All, [
       "GL_NV_command_list", // Additional extension we want to use  
+      "GL_ARB_gl_spirv",    // SPIR-V shaders before 4.6, `SpecializeShader` falls back to it
     ]);
 
     if env::var("CARGO_FEATURE_DEBUG").is_ok() {
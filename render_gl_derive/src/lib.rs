@@ -5,7 +5,15 @@ extern crate proc_macro;
 extern crate syn;
 #[macro_use] extern crate quote;
 
-#[proc_macro_derive(VertexAttribPointers, attributes())]
+/// Implements `render_gl::data::VertexAttribPointers` for a struct whose fields
+/// are all `render_gl::data::VertexAttribute`s.
+///
+/// The generated code names the traits as `crate::render_gl::data::...`, so by
+/// default the struct must live in the crate that declares `render_gl` at its
+/// root. Other crates point it elsewhere with `#[vertex(crate = "path")]`,
+/// where `path::render_gl` is the `render_gl` module, e.g.
+/// `#[vertex(crate = "::gladius")]` once `render_gl` is reachable from there.
+#[proc_macro_derive(VertexAttribPointers, attributes(location, divisor, vertex))]
 pub fn vertex_attrib_pointers_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     // Construct a string representation of the type definition
     // let s = input.to_string();
@@ -22,28 +30,410 @@ pub fn vertex_attrib_pointers_derive(input: proc_macro::TokenStream) -> proc_mac
     gen.into()
 }
 
-fn generate_impl(ast: &syn::DeriveInput) -> proc_macro::TokenStream {
-    let gen = quote! {
-        impl Vertex {
+/// Implements `render_gl::block::ShaderBlock` by laying out the fields in order.
+/// Like `VertexAttribPointers`, `#[shader_block(crate = "path")]` overrides
+/// the default `crate` root the `render_gl` module is looked up from.
+#[proc_macro_derive(ShaderBlock, attributes(shader_block))]
+pub fn shader_block_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = syn::parse(input).unwrap();
+
+    generate_shader_block_impl(&ast).into()
+}
+
+/// `vertex_attrib_pointer` call, `vertex_array_attrib` call and
+/// `VertexAttribInfo` generated for a single field
+type FieldCalls = (
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+);
+
+fn generate_impl(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+    let ident = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let krate = match parse_crate_path(&ast.attrs, "vertex") {
+        Ok(krate) => krate,
+        Err(e) => return e.to_compile_error(),
+    };
+    let (calls, overlap_check) =
+        match generate_vertex_attrib_pointer_calls(&krate, &ast.attrs, &ast.data) {
+            Ok(calls) => calls,
+            Err(e) => return e.to_compile_error(),
+        };
+    let fields_vertex_attrib_pointer = calls.iter().map(|c| &c.0);
+    let fields_vertex_array_attrib = calls.iter().map(|c| &c.1);
+    let fields_vertex_attrib_info = calls.iter().map(|c| &c.2);
+
+    // Checked at compile time; generic field types are only known in the methods
+    let (module_check, method_check) = match overlap_check {
+        Some(check) if ast.generics.params.is_empty() => {
+            (quote! { const _: () = { #check }; }, quote! {})
+        }
+        Some(check) => (quote! {}, quote! { const { #check } }),
+        None => (quote! {}, quote! {}),
+    };
+
+    quote! {
+        #module_check
+
+        impl #impl_generics #krate::render_gl::data::VertexAttribPointers for #ident #ty_generics #where_clause {
             fn vertex_attrib_pointers(gl: &gl::Gl) {
-                let stride = std::mem::size_of::<Self>();   // Byte offset between consecutive attributes
+                #method_check
+                let stride = ::std::mem::size_of::<Self>();   // Byte offset between consecutive attributes
 
-                let location = 0;   // layout (location = 0)
-                let offset = 0; // Offset of the first component
+                #(#fields_vertex_attrib_pointer)*
+            }
 
-                unsafe {
-                    data::f32_f32_f32::vertex_attrib_pointer(gl, location, stride, offset);
-                }
+            fn vertex_array_attribs(gl: &gl::Gl, vao: gl::types::GLuint, vbo: gl::types::GLuint) {
+                #method_check
+                let stride = ::std::mem::size_of::<Self>();   // Byte offset between consecutive attributes
+
+                #(#fields_vertex_array_attrib)*
+            }
+
+            fn vertex_attribs() -> Vec<#krate::render_gl::data::VertexAttribInfo> {
+                #method_check
+                vec![#(#fields_vertex_attrib_info),*]
+            }
+        }
+    }
+}
+
+/// Generates one `vertex_attrib_pointer` call per struct field, paired with
+/// its direct state access `vertex_array_attrib` counterpart and a
+/// `VertexAttribInfo` describing the field.
+/// Locations come from `#[location = N]` field attributes, or follow
+/// field declaration order when no field has one. Declared locations come
+/// with assertions, returned separately, that no two fields overlap.
+/// A `#[divisor = N]` on a field, or on the whole struct for per-instance
+/// data, also sets the attribute's instance divisor.
+fn generate_vertex_attrib_pointer_calls(
+    krate: &syn::Path,
+    struct_attrs: &[syn::Attribute],
+    data: &syn::Data,
+) -> Result<(Vec<FieldCalls>, Option<proc_macro2::TokenStream>), syn::Error> {
+    let fields = match data {
+        syn::Data::Struct(data_struct) => &data_struct.fields,
+        syn::Data::Enum(data_enum) => {
+            return Err(syn::Error::new(
+                data_enum.enum_token.span,
+                "VertexAttribPointers can only be derived for structs",
+            ))
+        }
+        syn::Data::Union(data_union) => {
+            return Err(syn::Error::new(
+                data_union.union_token.span,
+                "VertexAttribPointers can only be derived for structs",
+            ))
+        }
+    };
+
+    let locations = fields
+        .iter()
+        .map(|field| parse_usize_attr(&field.attrs, "location"))
+        .collect::<Result<Vec<Option<usize>>, syn::Error>>()?;
+
+    // Field divisors override the struct one
+    let struct_divisor = parse_usize_attr(struct_attrs, "divisor")?;
+    let divisors = fields
+        .iter()
+        .map(|field| Ok(parse_usize_attr(&field.attrs, "divisor")?.or(struct_divisor)))
+        .collect::<Result<Vec<Option<usize>>, syn::Error>>()?;
+
+    // Without any declared location, each field takes the locations
+    // following the ones of the field before it
+    if locations.iter().all(Option::is_none) {
+        let mut next = quote! { 0usize };
+        let mut resolved = Vec::with_capacity(fields.len());
+        for field in fields.iter() {
+            let field_ty = &field.ty;
+            resolved.push(next.clone());
+            next = quote! {
+                #next + <#field_ty as #krate::render_gl::data::VertexAttribute>::LOCATIONS
+            };
+        }
+        return Ok((generate_field_calls(krate, fields, resolved, divisors), None));
+    }
+
+    // Otherwise every field declares its location
+    let mut resolved = Vec::with_capacity(fields.len());
+    for (field, location) in fields.iter().zip(locations) {
+        match location {
+            Some(location) => resolved.push(location),
+            None => {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "missing `#[location = N]` attribute, required when other fields declare one",
+                ))
+            }
+        }
+    }
+    let locations = resolved;
+
+    // Two attributes can not share a location
+    for (i, field) in fields.iter().enumerate() {
+        if locations[..i].contains(&locations[i]) {
+            return Err(syn::Error::new_spanned(
+                field,
+                format!("duplicate vertex attribute location {}", locations[i]),
+            ));
+        }
+    }
+
+    // Nor overlap, which only the field types' `LOCATIONS` tell
+    let mut overlap_checks = Vec::new();
+    for (i, first) in fields.iter().enumerate() {
+        for (j, second) in fields.iter().enumerate().skip(i + 1) {
+            let (first_ty, first_location) = (&first.ty, locations[i]);
+            let (second_ty, second_location) = (&second.ty, locations[j]);
+            let message = format!(
+                "vertex attributes `{}` and `{}` overlap",
+                field_name(first, i),
+                field_name(second, j),
+            );
+            overlap_checks.push(quote! {
+                assert!(
+                    !#krate::render_gl::data::locations_overlap(
+                        #first_location,
+                        <#first_ty as #krate::render_gl::data::VertexAttribute>::LOCATIONS,
+                        #second_location,
+                        <#second_ty as #krate::render_gl::data::VertexAttribute>::LOCATIONS,
+                    ),
+                    #message
+                );
+            });
+        }
+    }
+    let overlap_check = quote! { #(#overlap_checks)* };
 
-                let location = 1;
-                let offset = offset + std::mem::size_of::<data::f32_f32_f32>(); // offset of first component
+    let locations = locations
+        .into_iter()
+        .map(|location| quote! { #location })
+        .collect();
 
-                unsafe {
-                    data::f32_f32_f32::vertex_attrib_pointer(gl, location, stride, offset);
+    Ok((generate_field_calls(krate, fields, locations, divisors), Some(overlap_check)))
+}
+
+fn generate_field_calls(
+    krate: &syn::Path,
+    fields: &syn::Fields,
+    locations: Vec<proc_macro2::TokenStream>,
+    divisors: Vec<Option<usize>>,
+) -> Vec<FieldCalls> {
+    fields
+        .iter()
+        .enumerate()
+        .zip(locations.into_iter().zip(divisors))
+        .map(|((index, field), (location, divisor))| {
+            generate_struct_field_vertex_attrib_pointer_call(krate, index, location, divisor, field)
+        })
+        .collect()
+}
+
+/// Field name, or index for tuple struct fields
+fn field_name(field: &syn::Field, index: usize) -> String {
+    match &field.ident {
+        Some(ident) => ident.to_string(),
+        None => index.to_string(),
+    }
+}
+
+/// Path given by a `#[name(crate = "path")]` attribute, `crate` without one
+fn parse_crate_path(attrs: &[syn::Attribute], name: &str) -> Result<syn::Path, syn::Error> {
+    let mut krate = None;
+
+    for attr in attrs.iter().filter(|a| a.path.is_ident(name)) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    format!("expected `#[{}(crate = \"path\")]`", name),
+                ))
+            }
+        };
+
+        for nested in list.nested.iter() {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit),
+                    ..
+                })) if path.is_ident("crate") => {
+                    if krate.is_some() {
+                        return Err(syn::Error::new_spanned(
+                            nested,
+                            "more than one `crate = \"path\"`",
+                        ));
+                    }
+                    krate = Some(lit.parse::<syn::Path>()?);
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        format!("expected `#[{}(crate = \"path\")]`", name),
+                    ))
                 }
             }
         }
+    }
+
+    Ok(krate.unwrap_or_else(|| syn::parse_quote!(crate)))
+}
+
+/// Reads the value of a `#[name = N]` attribute, if there is one
+fn parse_usize_attr(attrs: &[syn::Attribute], name: &str) -> Result<Option<usize>, syn::Error> {
+    let mut value = None;
+
+    for attr in attrs.iter().filter(|a| a.path.is_ident(name)) {
+        if value.is_some() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                format!("more than one `#[{} = N]` attribute", name),
+            ));
+        }
+
+        value = match attr.parse_meta()? {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Int(lit),
+                ..
+            }) => Some(lit.base10_parse::<usize>()?),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    format!("expected `#[{} = N]` with an integer value", name),
+                ))
+            }
+        };
+    }
+
+    Ok(value)
+}
+
+fn generate_struct_field_vertex_attrib_pointer_call(
+    krate: &syn::Path,
+    index: usize,
+    location: proc_macro2::TokenStream,
+    divisor: Option<usize>,
+    field: &syn::Field,
+) -> FieldCalls {
+    let field_ty = &field.ty;
+
+    // Named fields are addressed by name, tuple struct fields by index
+    let field_member = match &field.ident {
+        Some(ident) => quote! { #ident },
+        None => {
+            let index = syn::Index::from(index);
+            quote! { #index }
+        }
     };
 
-    gen.into()
-}
\ No newline at end of file
+    // Advance the attribute once per `divisor` instances instead of once per vertex
+    let divisor = divisor.map(|divisor| divisor as u32);
+    let divisor_call = divisor.map(|divisor| {
+        quote! {
+            unsafe {
+                gl.VertexAttribDivisor(location as gl::types::GLuint, #divisor);
+            }
+        }
+    });
+    let binding_divisor_call = divisor.map(|divisor| {
+        quote! {
+            unsafe {
+                gl.VertexArrayBindingDivisor(vao, location as gl::types::GLuint, #divisor);
+            }
+        }
+    });
+
+    let vertex_attrib_pointer_call = quote! {
+        let location = #location;   // layout (location = N)
+        let offset = ::std::mem::offset_of!(Self, #field_member); // Offset of the first component
+
+        unsafe {
+            <#field_ty as #krate::render_gl::data::VertexAttribute>::vertex_attrib_pointer(
+                gl, location, stride, offset,
+            );
+        }
+
+        #divisor_call
+    };
+
+    let vertex_array_attrib_call = quote! {
+        let location = #location;   // layout (location = N)
+        let offset = ::std::mem::offset_of!(Self, #field_member); // Offset of the first component
+
+        unsafe {
+            <#field_ty as #krate::render_gl::data::VertexAttribute>::vertex_array_attrib(
+                gl, vao, vbo, location, stride, offset,
+            );
+        }
+
+        #binding_divisor_call
+    };
+
+    let field_name = field_name(field, index);
+    let vertex_attrib_info = quote! {
+        #krate::render_gl::data::VertexAttribInfo {
+            name: #field_name,
+            location: #location,
+            shader_type: <#field_ty as #krate::render_gl::data::VertexAttribute>::SHADER_TYPE,
+        }
+    };
+
+    (vertex_attrib_pointer_call, vertex_array_attrib_call, vertex_attrib_info)
+}
+
+/// Lays the struct out like a GLSL struct: every field at its own base
+/// alignment, the whole struct aligned (and padded) to its largest member.
+fn generate_shader_block_impl(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+    let ident = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let fields = match &ast.data {
+        syn::Data::Struct(data_struct) => &data_struct.fields,
+        _ => {
+            return syn::Error::new_spanned(ident, "ShaderBlock can only be derived for structs")
+                .to_compile_error()
+        }
+    };
+
+    let krate = match parse_crate_path(&ast.attrs, "shader_block") {
+        Ok(krate) => krate,
+        Err(e) => return e.to_compile_error(),
+    };
+
+    let field_types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let field_members = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = syn::Index::from(index);
+                quote! { #index }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    quote! {
+        impl #impl_generics #krate::render_gl::block::ShaderBlock for #ident #ty_generics #where_clause {
+            fn base_alignment(layout: #krate::render_gl::block::BlockLayout) -> usize {
+                let alignment = 1;
+                #(
+                    let alignment = alignment.max(
+                        <#field_types as #krate::render_gl::block::ShaderBlock>::base_alignment(layout),
+                    );
+                )*
+                layout.aggregate_alignment(alignment)
+            }
+
+            fn write_block(&self, writer: &mut #krate::render_gl::block::BlockWriter) {
+                #(
+                    writer.write(&self.#field_members);
+                )*
+                writer.align(<Self as #krate::render_gl::block::ShaderBlock>::base_alignment(writer.layout()));
+            }
+        }
+    }
+}
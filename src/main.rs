@@ -24,6 +24,11 @@ fn main() {
 fn run() -> Result<()> {
     println!("Starting up..");
 
+    // Debug builds read the assets in the source tree, so edited shaders reach
+    // `ReloadableProgram::poll` without a rebuild copying them next to the exe
+    #[cfg(debug_assertions)]
+    let res = Resources::from_path(&Path::new(env!("CARGO_MANIFEST_DIR")).join("assets"));
+    #[cfg(not(debug_assertions))]
     let res = Resources::from_relative_exe_path(Path::new("assets")).unwrap();
 
     let sdl = sdl2::init().map_err(|message| Error::msg(message))?;
@@ -51,7 +56,10 @@ fn run() -> Result<()> {
     
     let mut viewport = render_gl::Viewport::for_window(900, 700);
     let color_buffer = render_gl::ColorBuffer::from_color(na::Vector3::new(0.3, 0.3, 0.5));
-    let triangle = triangle::Triangle::new(&res, &gl)?;
+    let mut triangle = triangle::Triangle::new(&res, &gl)?;
+    for diagnostic in triangle.shader_diagnostics() {
+        println!("{}", diagnostic);
+    }
 
     // Setup shared state for window
 
@@ -78,12 +86,24 @@ fn run() -> Result<()> {
             }
         }
 
+        // Hot reload edited shaders
+        match triangle.reload_shaders(&res) {
+            Ok(true) => {
+                println!("Reloaded triangle shaders");
+                for diagnostic in triangle.shader_diagnostics() {
+                    println!("{}", diagnostic);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => println!("@ERROR: {}", e),
+        }
+
         // Render
         unsafe {
             gl.Clear(gl::COLOR_BUFFER_BIT);
         }
 
-        triangle.render(&gl);
+        triangle.render();
 
         // Present rendered buffer
         window.gl_swap_window();
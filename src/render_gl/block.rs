@@ -0,0 +1,308 @@
+use nalgebra as na;
+
+/// Memory layout rules for `uniform` and `buffer` blocks
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockLayout {
+    /// `layout (std140)`, the only layout uniform blocks can share between programs
+    Std140,
+    /// `layout (std430)`, tighter packing only available to shader storage blocks
+    Std430,
+}
+
+impl BlockLayout {
+    /// Alignment of arrays and structs whose members align to `alignment`.
+    /// std140 rounds them up to the alignment of a `vec4`.
+    pub fn aggregate_alignment(self, alignment: usize) -> usize {
+        match self {
+            BlockLayout::Std140 => round_up(alignment, 16),
+            BlockLayout::Std430 => alignment,
+        }
+    }
+}
+
+/// A type that can be a member of a `uniform` or `buffer` block,
+/// usually implemented for structs with `#[derive(ShaderBlock)]`.
+pub trait ShaderBlock {
+    /// Base alignment of the type in bytes
+    fn base_alignment(layout: BlockLayout) -> usize;
+
+    /// Writes the value at the current (already aligned) writer position
+    fn write_block(&self, writer: &mut BlockWriter);
+
+    /// Lays the value out as a whole block, ready to be uploaded with
+    /// e.g. `UniformBuffer::dynamic_draw_data`
+    fn to_block_bytes(&self, layout: BlockLayout) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut writer = BlockWriter::new(layout);
+        writer.write(self);
+        writer.into_bytes()
+    }
+
+    fn to_std140(&self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        self.to_block_bytes(BlockLayout::Std140)
+    }
+
+    fn to_std430(&self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        self.to_block_bytes(BlockLayout::Std430)
+    }
+}
+
+/// Byte buffer that inserts the padding required by a block layout
+pub struct BlockWriter {
+    layout: BlockLayout,
+    bytes: Vec<u8>,
+}
+
+impl BlockWriter {
+    pub fn new(layout: BlockLayout) -> BlockWriter {
+        BlockWriter {
+            layout,
+            bytes: Vec::new(),
+        }
+    }
+
+    pub fn layout(&self) -> BlockLayout {
+        self.layout
+    }
+
+    /// Pads with zeroes until the position is a multiple of `alignment`
+    pub fn align(&mut self, alignment: usize) {
+        let len = round_up(self.bytes.len(), alignment);
+        self.bytes.resize(len, 0);
+    }
+
+    /// Aligns to the base alignment of `T` and writes `value`
+    pub fn write<T: ShaderBlock>(&mut self, value: &T) {
+        self.align(T::base_alignment(self.layout));
+        value.write_block(self);
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+fn round_up(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+macro_rules! scalar_shader_block {
+    ($t:ty) => {
+        impl ShaderBlock for $t {
+            fn base_alignment(_layout: BlockLayout) -> usize {
+                ::std::mem::size_of::<$t>()
+            }
+
+            fn write_block(&self, writer: &mut BlockWriter) {
+                writer.write_bytes(&self.to_ne_bytes());
+            }
+        }
+    };
+}
+
+scalar_shader_block!(f32);
+scalar_shader_block!(f64);
+scalar_shader_block!(i32);
+scalar_shader_block!(u32);
+
+/// GLSL `bool` is 4 bytes wide in blocks
+impl ShaderBlock for bool {
+    fn base_alignment(_layout: BlockLayout) -> usize {
+        4
+    }
+
+    fn write_block(&self, writer: &mut BlockWriter) {
+        writer.write_bytes(&(*self as u32).to_ne_bytes());
+    }
+}
+
+/// `vec2` aligns to twice its component size, `vec3` and `vec4` to four times
+macro_rules! vector_shader_block {
+    ($t:ty, $vector:ident, $alignment_components:expr) => {
+        impl ShaderBlock for na::$vector<$t> {
+            fn base_alignment(_layout: BlockLayout) -> usize {
+                $alignment_components * ::std::mem::size_of::<$t>()
+            }
+
+            fn write_block(&self, writer: &mut BlockWriter) {
+                for component in self.iter() {
+                    component.write_block(writer);
+                }
+            }
+        }
+    };
+}
+
+vector_shader_block!(f32, Vector2, 2);
+vector_shader_block!(f32, Vector3, 4);
+vector_shader_block!(f32, Vector4, 4);
+vector_shader_block!(f64, Vector2, 2);
+vector_shader_block!(f64, Vector3, 4);
+vector_shader_block!(f64, Vector4, 4);
+vector_shader_block!(i32, Vector2, 2);
+vector_shader_block!(i32, Vector3, 4);
+vector_shader_block!(i32, Vector4, 4);
+vector_shader_block!(u32, Vector2, 2);
+vector_shader_block!(u32, Vector3, 4);
+vector_shader_block!(u32, Vector4, 4);
+
+/// Column major matrices are laid out like an array of their column vectors
+macro_rules! matrix_shader_block {
+    ($t:ty, $matrix:ident, $column:ident) => {
+        impl ShaderBlock for na::$matrix<$t> {
+            fn base_alignment(layout: BlockLayout) -> usize {
+                layout.aggregate_alignment(na::$column::<$t>::base_alignment(layout))
+            }
+
+            fn write_block(&self, writer: &mut BlockWriter) {
+                let alignment = Self::base_alignment(writer.layout());
+
+                for column in self.column_iter() {
+                    writer.align(alignment);
+                    column.into_owned().write_block(writer);
+                }
+                writer.align(alignment);
+            }
+        }
+    };
+}
+
+matrix_shader_block!(f32, Matrix2, Vector2);
+matrix_shader_block!(f32, Matrix3, Vector3);
+matrix_shader_block!(f32, Matrix4, Vector4);
+matrix_shader_block!(f64, Matrix2, Vector2);
+matrix_shader_block!(f64, Matrix3, Vector3);
+matrix_shader_block!(f64, Matrix4, Vector4);
+
+/// Each array element starts at a multiple of the array alignment
+impl<T: ShaderBlock, const N: usize> ShaderBlock for [T; N] {
+    fn base_alignment(layout: BlockLayout) -> usize {
+        layout.aggregate_alignment(T::base_alignment(layout))
+    }
+
+    fn write_block(&self, writer: &mut BlockWriter) {
+        let alignment = Self::base_alignment(writer.layout());
+
+        for element in self.iter() {
+            writer.align(alignment);
+            element.write_block(writer);
+        }
+        writer.align(alignment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `f32` stored at `offset` of `bytes`
+    fn f32_at(bytes: &[u8], offset: usize) -> f32 {
+        let mut value = [0; 4];
+        value.copy_from_slice(&bytes[offset..offset + 4]);
+        f32::from_ne_bytes(value)
+    }
+
+    #[derive(ShaderBlock)]
+    struct Vec3Float {
+        a: na::Vector3<f32>,
+        b: f32,
+    }
+
+    #[test]
+    fn float_fills_vec3_padding() {
+        let block = Vec3Float {
+            a: na::Vector3::new(1.0, 2.0, 3.0),
+            b: 4.0,
+        };
+
+        for bytes in [block.to_std140(), block.to_std430()] {
+            assert_eq!(bytes.len(), 16);
+            assert_eq!(f32_at(&bytes, 8), 3.0);
+            assert_eq!(f32_at(&bytes, 12), 4.0);
+        }
+    }
+
+    #[test]
+    fn mat3_columns_are_vec4_apart() {
+        let matrix = na::Matrix3::new(1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0);
+
+        for bytes in [matrix.to_std140(), matrix.to_std430()] {
+            assert_eq!(bytes.len(), 48);
+            // Column major, the first column holds the first element of each row
+            assert_eq!(f32_at(&bytes, 0), 1.0);
+            assert_eq!(f32_at(&bytes, 16), 2.0);
+            assert_eq!(f32_at(&bytes, 32), 3.0);
+            assert_eq!(f32_at(&bytes, 36), 6.0);
+        }
+    }
+
+    #[test]
+    fn float_array_stride() {
+        let array = [1.0f32, 2.0, 3.0];
+
+        let std140 = array.to_std140();
+        assert_eq!(std140.len(), 48);
+        assert_eq!(f32_at(&std140, 16), 2.0);
+        assert_eq!(f32_at(&std140, 32), 3.0);
+
+        let std430 = array.to_std430();
+        assert_eq!(std430.len(), 12);
+        assert_eq!(f32_at(&std430, 4), 2.0);
+        assert_eq!(f32_at(&std430, 8), 3.0);
+    }
+
+    #[test]
+    fn vec3_array_stride() {
+        let array = [na::Vector3::new(1.0f32, 2.0, 3.0), na::Vector3::new(4.0, 5.0, 6.0)];
+
+        for bytes in [array.to_std140(), array.to_std430()] {
+            assert_eq!(bytes.len(), 32);
+            assert_eq!(f32_at(&bytes, 8), 3.0);
+            assert_eq!(f32_at(&bytes, 16), 4.0);
+        }
+    }
+
+    #[derive(ShaderBlock)]
+    struct Inner {
+        a: f32,
+    }
+
+    #[derive(ShaderBlock)]
+    struct Outer {
+        x: f32,
+        inner: Inner,
+        y: f32,
+    }
+
+    #[test]
+    fn nested_struct_padding() {
+        let block = Outer {
+            x: 1.0,
+            inner: Inner { a: 2.0 },
+            y: 3.0,
+        };
+
+        // std140 aligns and pads the nested struct to a vec4
+        let std140 = block.to_std140();
+        assert_eq!(std140.len(), 48);
+        assert_eq!(f32_at(&std140, 16), 2.0);
+        assert_eq!(f32_at(&std140, 32), 3.0);
+
+        let std430 = block.to_std430();
+        assert_eq!(std430.len(), 12);
+        assert_eq!(f32_at(&std430, 4), 2.0);
+        assert_eq!(f32_at(&std430, 8), 3.0);
+    }
+}
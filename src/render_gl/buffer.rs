@@ -1,4 +1,22 @@
+use crate::render_gl::capabilities;
+use crate::render_gl::data::{Pod, VertexAttribPointers};
 use gl;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Failed to map {} bytes of buffer {} at offset {}", size, buffer, offset)]
+    MapFailed {
+        buffer: gl::types::GLuint,
+        offset: usize,
+        size: usize,
+    },
+    #[error("Mapping access {:#x} lacks the required bits {:#x}", access, required)]
+    MapAccess {
+        access: gl::types::GLbitfield,
+        required: gl::types::GLbitfield,
+    },
+}
 
 pub trait BufferType {
     const BUFFER_TYPE: gl::types::GLuint;
@@ -17,40 +35,131 @@ impl BufferType for BufferTypeElementArray {
 }
 pub type ElementArrayBuffer = Buffer<BufferTypeElementArray>;
 
+/// Uniform Buffer, backs `uniform` blocks
+pub struct BufferTypeUniform;
+impl BufferType for BufferTypeUniform {
+    const BUFFER_TYPE: gl::types::GLuint = gl::UNIFORM_BUFFER;
+}
+impl IndexedBufferType for BufferTypeUniform {}
+pub type UniformBuffer = Buffer<BufferTypeUniform>;
+
+/// Shader Storage Buffer, backs `buffer` blocks
+pub struct BufferTypeShaderStorage;
+impl BufferType for BufferTypeShaderStorage {
+    const BUFFER_TYPE: gl::types::GLuint = gl::SHADER_STORAGE_BUFFER;
+}
+impl IndexedBufferType for BufferTypeShaderStorage {}
+pub type ShaderStorageBuffer = Buffer<BufferTypeShaderStorage>;
+
+/// Draw Indirect Buffer, holds parameters for `Draw*Indirect` calls
+pub struct BufferTypeDrawIndirect;
+impl BufferType for BufferTypeDrawIndirect {
+    const BUFFER_TYPE: gl::types::GLuint = gl::DRAW_INDIRECT_BUFFER;
+}
+pub type DrawIndirectBuffer = Buffer<BufferTypeDrawIndirect>;
+
+/// Dispatch Indirect Buffer, holds parameters for `DispatchComputeIndirect` calls
+pub struct BufferTypeDispatchIndirect;
+impl BufferType for BufferTypeDispatchIndirect {
+    const BUFFER_TYPE: gl::types::GLuint = gl::DISPATCH_INDIRECT_BUFFER;
+}
+pub type DispatchIndirectBuffer = Buffer<BufferTypeDispatchIndirect>;
+
+/// Pixel Pack Buffer, destination of pixel reads
+pub struct BufferTypePixelPack;
+impl BufferType for BufferTypePixelPack {
+    const BUFFER_TYPE: gl::types::GLuint = gl::PIXEL_PACK_BUFFER;
+}
+pub type PixelPackBuffer = Buffer<BufferTypePixelPack>;
+
+/// Pixel Unpack Buffer, source of texture uploads
+pub struct BufferTypePixelUnpack;
+impl BufferType for BufferTypePixelUnpack {
+    const BUFFER_TYPE: gl::types::GLuint = gl::PIXEL_UNPACK_BUFFER;
+}
+pub type PixelUnpackBuffer = Buffer<BufferTypePixelUnpack>;
+
+/// Copy Read Buffer, source of buffer to buffer copies
+pub struct BufferTypeCopyRead;
+impl BufferType for BufferTypeCopyRead {
+    const BUFFER_TYPE: gl::types::GLuint = gl::COPY_READ_BUFFER;
+}
+pub type CopyReadBuffer = Buffer<BufferTypeCopyRead>;
+
+/// Copy Write Buffer, destination of buffer to buffer copies
+pub struct BufferTypeCopyWrite;
+impl BufferType for BufferTypeCopyWrite {
+    const BUFFER_TYPE: gl::types::GLuint = gl::COPY_WRITE_BUFFER;
+}
+pub type CopyWriteBuffer = Buffer<BufferTypeCopyWrite>;
+
+/// Buffer targets with indexed binding points that shaders read blocks from
+pub trait IndexedBufferType: BufferType {}
+
+/// Integer type that can be stored in an `ElementArrayBuffer`
+pub trait IndexType: Copy {
+    /// Type passed to `DrawElements`
+    const GL_TYPE: gl::types::GLenum;
+
+    /// Index that restarts the primitive when primitive restart is enabled,
+    /// always the largest value of the type
+    const RESTART_INDEX: Self;
+}
+
+impl IndexType for u8 {
+    const GL_TYPE: gl::types::GLenum = gl::UNSIGNED_BYTE;
+    const RESTART_INDEX: u8 = u8::MAX;
+}
+
+impl IndexType for u16 {
+    const GL_TYPE: gl::types::GLenum = gl::UNSIGNED_SHORT;
+    const RESTART_INDEX: u16 = u16::MAX;
+}
+
+impl IndexType for u32 {
+    const GL_TYPE: gl::types::GLenum = gl::UNSIGNED_INT;
+    const RESTART_INDEX: u32 = u32::MAX;
+}
+
 pub struct Buffer<B>
 where
     B: BufferType,
 {
     gl: gl::Gl,
     vbo: gl::types::GLuint,
+    // Edit with the `Named*` direct state access calls instead of binding
+    dsa: bool,
     _marker: ::std::marker::PhantomData<B>,
 }
 
-// @TODO: See if storing multiple buffers in
-// single struct is more efficient for my use case
-pub struct ArrayBuffers {
-    gl: gl::Gl,
-    vbo: Vec<gl::types::GLuint>,
-}
-// ~@TODO
-
 impl<B> Buffer<B>
 where
     B: BufferType,
 {
     pub fn new(gl: &gl::Gl) -> Buffer<B> {
+        let dsa = capabilities::has_direct_state_access(gl);
+
         let mut vbo: gl::types::GLuint = 0;
         unsafe {
-            gl.GenBuffers(1, &mut vbo);
+            if dsa {
+                gl.CreateBuffers(1, &mut vbo);
+            } else {
+                gl.GenBuffers(1, &mut vbo);
+            }
         }
 
         Buffer {
             gl: gl.clone(),
             vbo,
+            dsa,
             _marker: ::std::marker::PhantomData,
         }
     }
 
+    pub fn id(&self) -> gl::types::GLuint {
+        self.vbo
+    }
+
     pub fn bind(&self) {
         unsafe {
             self.gl.BindBuffer(B::BUFFER_TYPE, self.vbo);
@@ -63,14 +172,431 @@ where
         }
     }
 
+    /// Uploads data that is set once and drawn many times
     pub fn static_draw_data<T>(&self, data: &[T]) {
+        self.buffer_data(data, gl::STATIC_DRAW);
+    }
+
+    /// Uploads data that is modified repeatedly and drawn many times
+    pub fn dynamic_draw_data<T>(&self, data: &[T]) {
+        self.buffer_data(data, gl::DYNAMIC_DRAW);
+    }
+
+    /// Uploads data that is set once and drawn a few times, e.g. once per frame
+    pub fn stream_draw_data<T>(&self, data: &[T]) {
+        self.buffer_data(data, gl::STREAM_DRAW);
+    }
+
+    /// Allocates room for `len` elements of `T` without uploading anything
+    pub fn reserve<T>(&self, len: usize, usage: gl::types::GLenum) {
+        self.buffer_data_raw(len * ::std::mem::size_of::<T>(), ::std::ptr::null(), usage);
+    }
+
+    /// Re-allocates the buffer with its current size and usage, so the driver
+    /// can hand out fresh storage instead of waiting for draws still reading
+    /// the old contents
+    pub fn orphan(&self) {
+        let usage = self.parameter(gl::BUFFER_USAGE) as gl::types::GLenum;
+        let size = self.size() as gl::types::GLsizeiptr;
+
+        unsafe {
+            if self.dsa {
+                self.gl.NamedBufferData(self.vbo, size, ::std::ptr::null(), usage);
+            } else {
+                // Doesn't rely on the buffer being bound to its own target
+                self.with_copy_write_binding(|target| {
+                    self.gl.BufferData(target, size, ::std::ptr::null(), usage)
+                });
+            }
+        }
+    }
+
+    /// Overwrites part of the buffer, starting `offset` elements of `T` in
+    pub fn sub_data<T>(&self, offset: usize, data: &[T]) {
+        let offset = (offset * ::std::mem::size_of::<T>()) as gl::types::GLintptr; // offset in bytes
+        let size = ::std::mem::size_of_val(data) as gl::types::GLsizeiptr; // size of data in bytes
+        let data = data.as_ptr() as *const gl::types::GLvoid; // pointer to data
+
+        unsafe {
+            if self.dsa {
+                self.gl.NamedBufferSubData(self.vbo, offset, size, data)
+            } else {
+                self.gl.BufferSubData(B::BUFFER_TYPE, offset, size, data)
+            }
+        }
+    }
+
+    /// Allocates immutable storage initialized with `data`. The storage can
+    /// not be re-allocated afterwards, `flags` (`gl::DYNAMIC_STORAGE_BIT`,
+    /// `gl::MAP_WRITE_BIT`, ...) decide how it can still be updated.
+    pub fn storage_data<T>(&self, data: &[T], flags: gl::types::GLbitfield) {
+        self.buffer_storage_raw(
+            ::std::mem::size_of_val(data),
+            data.as_ptr() as *const gl::types::GLvoid,
+            flags,
+        );
+    }
+
+    /// Allocates uninitialized immutable storage for `len` elements of `T`
+    pub fn storage<T>(&self, len: usize, flags: gl::types::GLbitfield) {
+        self.buffer_storage_raw(len * ::std::mem::size_of::<T>(), ::std::ptr::null(), flags);
+    }
+
+    fn buffer_data<T>(&self, data: &[T], usage: gl::types::GLenum) {
+        self.buffer_data_raw(
+            ::std::mem::size_of_val(data),
+            data.as_ptr() as *const gl::types::GLvoid,
+            usage,
+        );
+    }
+
+    fn buffer_data_raw(&self, size: usize, data: *const gl::types::GLvoid, usage: gl::types::GLenum) {
+        unsafe {
+            if self.dsa {
+                self.gl
+                    .NamedBufferData(self.vbo, size as gl::types::GLsizeiptr, data, usage)
+            } else {
+                self.gl.BufferData(
+                    B::BUFFER_TYPE,                 // target
+                    size as gl::types::GLsizeiptr,  // size of data in bytes
+                    data,                           // pointer to data
+                    usage,                          // usage
+                )
+            }
+        }
+    }
+
+    fn buffer_storage_raw(
+        &self,
+        size: usize,
+        data: *const gl::types::GLvoid,
+        flags: gl::types::GLbitfield,
+    ) {
+        unsafe {
+            if self.dsa {
+                self.gl
+                    .NamedBufferStorage(self.vbo, size as gl::types::GLsizeiptr, data, flags)
+            } else {
+                self.gl.BufferStorage(
+                    B::BUFFER_TYPE,                 // target
+                    size as gl::types::GLsizeiptr,  // size of data in bytes
+                    data,                           // pointer to data
+                    flags,                          // storage flags
+                )
+            }
+        }
+    }
+
+    /// Size of the buffer's data store in bytes
+    pub fn size(&self) -> usize {
+        self.parameter(gl::BUFFER_SIZE) as usize
+    }
+
+    fn parameter(&self, pname: gl::types::GLenum) -> gl::types::GLint64 {
+        let mut value: gl::types::GLint64 = 0;
+        unsafe {
+            if self.dsa {
+                self.gl.GetNamedBufferParameteri64v(self.vbo, pname, &mut value);
+            } else {
+                self.with_copy_write_binding(|target| {
+                    self.gl.GetBufferParameteri64v(target, pname, &mut value)
+                });
+            }
+        }
+        value
+    }
+
+    /// Runs `f` with the buffer bound to `COPY_WRITE_BUFFER`, which no draw
+    /// reads from, and binds back whatever was bound there before. Binding to
+    /// the buffer's own target would change what draws read from, or for
+    /// element arrays the index buffer of the bound vertex array.
+    unsafe fn with_copy_write_binding<R>(&self, f: impl FnOnce(gl::types::GLenum) -> R) -> R {
+        let mut previous: gl::types::GLint = 0;
+        self.gl.GetIntegerv(gl::COPY_WRITE_BUFFER_BINDING, &mut previous);
+
+        self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, self.vbo);
+        let result = f(gl::COPY_WRITE_BUFFER);
+        self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, previous as gl::types::GLuint);
+
+        result
+    }
+
+    /// Maps the whole buffer, as elements of `T`, for reading
+    pub fn map_read<T: Pod>(&mut self) -> Result<MappedBuffer<'_, B, T>, Error> {
+        let len = self.size() / element_size::<T>();
+        let ptr = self.map_range_ptr::<T>(0, len, gl::MAP_READ_BIT)?;
+
+        Ok(MappedBuffer {
+            buffer: self,
+            ptr,
+            len,
+        })
+    }
+
+    /// Maps the whole buffer, as elements of `T`, for writing.
+    /// The previous contents are discarded.
+    pub fn map_write<T: Pod>(&mut self) -> Result<MappedBufferWrite<'_, B, T>, Error> {
+        let len = self.size() / element_size::<T>();
+        self.map_range_write(0, len, gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_BUFFER_BIT)
+    }
+
+    /// Maps `len` elements of `T` starting `offset` elements in, for reading
+    /// and writing. `access` is passed to `MapBufferRange` and has to contain
+    /// both `MAP_READ_BIT` and `MAP_WRITE_BIT`.
+    pub fn map_range<T: Pod>(
+        &mut self,
+        offset: usize,
+        len: usize,
+        access: gl::types::GLbitfield,
+    ) -> Result<MappedBufferMut<'_, B, T>, Error> {
+        check_map_access(access, gl::MAP_READ_BIT | gl::MAP_WRITE_BIT)?;
+        let ptr = self.map_range_ptr::<T>(offset, len, access)?;
+
+        Ok(MappedBufferMut {
+            buffer: self,
+            ptr,
+            len,
+        })
+    }
+
+    /// Like `map_range`, for writing only. `access` has to contain
+    /// `MAP_WRITE_BIT`, and may add e.g. `MAP_INVALIDATE_RANGE_BIT`.
+    pub fn map_range_write<T: Pod>(
+        &mut self,
+        offset: usize,
+        len: usize,
+        access: gl::types::GLbitfield,
+    ) -> Result<MappedBufferWrite<'_, B, T>, Error> {
+        check_map_access(access, gl::MAP_WRITE_BIT)?;
+        let ptr = self.map_range_ptr::<T>(offset, len, access)?;
+
+        Ok(MappedBufferWrite {
+            buffer: self,
+            ptr,
+            len,
+        })
+    }
+
+    pub(crate) fn map_range_ptr<T>(
+        &self,
+        offset: usize,
+        len: usize,
+        access: gl::types::GLbitfield,
+    ) -> Result<*mut T, Error> {
+        let offset = offset * element_size::<T>();
+        let size = len * element_size::<T>();
+
+        let ptr = unsafe {
+            if self.dsa {
+                self.gl.MapNamedBufferRange(
+                    self.vbo,
+                    offset as gl::types::GLintptr,
+                    size as gl::types::GLsizeiptr,
+                    access,
+                )
+            } else {
+                self.with_copy_write_binding(|target| {
+                    self.gl.MapBufferRange(
+                        target,                         // target
+                        offset as gl::types::GLintptr,  // offset in bytes
+                        size as gl::types::GLsizeiptr,  // length in bytes
+                        access,                         // access
+                    )
+                })
+            }
+        };
+
+        if ptr.is_null() {
+            return Err(Error::MapFailed {
+                buffer: self.vbo,
+                offset,
+                size,
+            });
+        }
+
+        Ok(ptr as *mut T)
+    }
+
+    pub(crate) fn unmap(&self) {
+        // Returns FALSE when the data store got corrupted while mapped
+        // (e.g. on a display mode change), nothing to do about it here
+        unsafe {
+            if self.dsa {
+                self.gl.UnmapNamedBuffer(self.vbo);
+            } else {
+                self.with_copy_write_binding(|target| self.gl.UnmapBuffer(target));
+            }
+        }
+    }
+}
+
+/// Size of a mapped element, a zero sized `T` can not index buffer memory
+const fn element_size<T>() -> usize {
+    const { assert!(::std::mem::size_of::<T>() != 0, "can not map zero sized elements") };
+    ::std::mem::size_of::<T>()
+}
+
+fn check_map_access(
+    access: gl::types::GLbitfield,
+    required: gl::types::GLbitfield,
+) -> Result<(), Error> {
+    if access & required != required {
+        return Err(Error::MapAccess { access, required });
+    }
+    Ok(())
+}
+
+/// Read-only view of a mapped buffer, unmapped when dropped
+pub struct MappedBuffer<'a, B, T>
+where
+    B: BufferType,
+{
+    // Borrowed mutably so nothing can re-allocate the data store while mapped
+    buffer: &'a mut Buffer<B>,
+    ptr: *mut T,
+    len: usize,
+}
+
+impl<'a, B, T> ::std::ops::Deref for MappedBuffer<'a, B, T>
+where
+    B: BufferType,
+{
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { ::std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<'a, B, T> Drop for MappedBuffer<'a, B, T>
+where
+    B: BufferType,
+{
+    fn drop(&mut self) {
+        self.buffer.unmap();
+    }
+}
+
+/// Mutable view of a buffer mapped for reading and writing, unmapped when dropped
+pub struct MappedBufferMut<'a, B, T>
+where
+    B: BufferType,
+{
+    buffer: &'a mut Buffer<B>,
+    ptr: *mut T,
+    len: usize,
+}
+
+impl<'a, B, T> ::std::ops::Deref for MappedBufferMut<'a, B, T>
+where
+    B: BufferType,
+{
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { ::std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<'a, B, T> ::std::ops::DerefMut for MappedBufferMut<'a, B, T>
+where
+    B: BufferType,
+{
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { ::std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl<'a, B, T> Drop for MappedBufferMut<'a, B, T>
+where
+    B: BufferType,
+{
+    fn drop(&mut self) {
+        self.buffer.unmap();
+    }
+}
+
+/// Write-only view of a mapped buffer, unmapped when dropped. The mapped
+/// memory may not hold the buffer contents, so it is never handed out as
+/// a slice that could be read.
+pub struct MappedBufferWrite<'a, B, T>
+where
+    B: BufferType,
+{
+    buffer: &'a mut Buffer<B>,
+    ptr: *mut T,
+    len: usize,
+}
+
+impl<'a, B, T> MappedBufferWrite<'a, B, T>
+where
+    B: BufferType,
+    T: Pod,
+{
+    /// Number of mapped elements
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Writes `value` at element `index`, panics when out of range
+    pub fn write(&mut self, index: usize, value: T) {
+        assert!(index < self.len, "index {} out of range for {} mapped elements", index, self.len);
+        unsafe { self.ptr.add(index).write(value) }
+    }
+
+    /// Writes `values` starting at element `offset`, panics when they don't fit
+    pub fn write_slice(&mut self, offset: usize, values: &[T]) {
+        assert!(
+            offset <= self.len && values.len() <= self.len - offset,
+            "{} elements at {} out of range for {} mapped elements",
+            values.len(),
+            offset,
+            self.len,
+        );
+        unsafe {
+            ::std::ptr::copy_nonoverlapping(values.as_ptr(), self.ptr.add(offset), values.len())
+        }
+    }
+}
+
+impl<'a, B, T> Drop for MappedBufferWrite<'a, B, T>
+where
+    B: BufferType,
+{
+    fn drop(&mut self) {
+        self.buffer.unmap();
+    }
+}
+
+impl<B> Buffer<B>
+where
+    B: IndexedBufferType,
+{
+    /// Binds the whole buffer to binding point `index`,
+    /// e.g. `layout (std140, binding = 0) uniform Camera { ... }`
+    pub fn bind_base(&self, index: usize) {
+        unsafe {
+            self.gl
+                .BindBufferBase(B::BUFFER_TYPE, index as gl::types::GLuint, self.vbo);
+        }
+    }
+
+    /// Binds `size` bytes of the buffer starting at byte `offset` to binding point `index`.
+    /// `offset` must be a multiple of `GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT`
+    /// (or `GL_SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT`).
+    pub fn bind_range(&self, index: usize, offset: usize, size: usize) {
         unsafe {
-            self.gl.BufferData(
-                gl::ARRAY_BUFFER,                                                   // target
-                (data.len() * ::std::mem::size_of::<T>()) as gl::types::GLsizeiptr, // size of data in bytes
-                data.as_ptr() as *const gl::types::GLvoid, // pointer to data
-                gl::STATIC_DRAW,                           // usage
-            )
+            self.gl.BindBufferRange(
+                B::BUFFER_TYPE,
+                index as gl::types::GLuint,
+                self.vbo,
+                offset as gl::types::GLintptr,
+                size as gl::types::GLsizeiptr,
+            );
         }
     }
 }
@@ -86,21 +612,290 @@ where
     }
 }
 
+/// Many buffers created with a single `GenBuffers` call
+/// and deleted with a single `DeleteBuffers` call
+pub struct Buffers<B>
+where
+    B: BufferType,
+{
+    gl: gl::Gl,
+    vbo: Vec<gl::types::GLuint>,
+    dsa: bool,
+    _marker: ::std::marker::PhantomData<B>,
+}
+
+pub type ArrayBuffers = Buffers<BufferTypeArray>;
+
+impl<B> Buffers<B>
+where
+    B: BufferType,
+{
+    pub fn new(gl: &gl::Gl, count: usize) -> Buffers<B> {
+        let dsa = capabilities::has_direct_state_access(gl);
+
+        let mut vbo: Vec<gl::types::GLuint> = vec![0; count];
+        unsafe {
+            if dsa {
+                gl.CreateBuffers(count as gl::types::GLsizei, vbo.as_mut_ptr());
+            } else {
+                gl.GenBuffers(count as gl::types::GLsizei, vbo.as_mut_ptr());
+            }
+        }
+
+        Buffers {
+            gl: gl.clone(),
+            vbo,
+            dsa,
+            _marker: ::std::marker::PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.vbo.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vbo.is_empty()
+    }
+
+    /// Binds the buffer at `index`. Panics if `index` is out of bounds
+    pub fn bind(&self, index: usize) {
+        unsafe {
+            self.gl.BindBuffer(B::BUFFER_TYPE, self.vbo[index]);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            self.gl.BindBuffer(B::BUFFER_TYPE, 0);
+        }
+    }
+
+    /// Borrows the buffer at `index`, giving access to all `Buffer` methods
+    pub fn get(&self, index: usize) -> Option<BufferSlot<'_, B>> {
+        self.vbo.get(index).map(|&vbo| BufferSlot {
+            buffer: ::std::mem::ManuallyDrop::new(Buffer {
+                gl: self.gl.clone(),
+                vbo,
+                dsa: self.dsa,
+                _marker: ::std::marker::PhantomData,
+            }),
+            _owner: ::std::marker::PhantomData,
+        })
+    }
+
+    /// Borrows the buffer at `index`. Panics if `index` is out of bounds
+    pub fn slot(&self, index: usize) -> BufferSlot<'_, B> {
+        let len = self.len();
+        self.get(index).unwrap_or_else(|| {
+            panic!("Buffer index {} out of bounds for {} buffers", index, len)
+        })
+    }
+
+    /// Uploads `data` to the buffer at `index`, binding it first
+    /// unless direct state access is available
+    pub fn static_draw_data<T>(&self, index: usize, data: &[T]) {
+        if !self.dsa {
+            self.bind(index);
+        }
+        self.slot(index).static_draw_data(data);
+    }
+
+    /// Uploads `data` to the buffer at `index`, binding it first
+    /// unless direct state access is available
+    pub fn dynamic_draw_data<T>(&self, index: usize, data: &[T]) {
+        if !self.dsa {
+            self.bind(index);
+        }
+        self.slot(index).dynamic_draw_data(data);
+    }
+
+    /// Uploads `data` to the buffer at `index`, binding it first
+    /// unless direct state access is available
+    pub fn stream_draw_data<T>(&self, index: usize, data: &[T]) {
+        if !self.dsa {
+            self.bind(index);
+        }
+        self.slot(index).stream_draw_data(data);
+    }
+}
+
+impl<B> Drop for Buffers<B>
+where
+    B: BufferType,
+{
+    fn drop(&mut self) {
+        unsafe {
+            self.gl
+                .DeleteBuffers(self.vbo.len() as gl::types::GLsizei, self.vbo.as_ptr());
+        }
+    }
+}
+
+/// A single buffer borrowed from `Buffers`. It dereferences to `Buffer`
+/// but does not delete the buffer when dropped, its owner does.
+pub struct BufferSlot<'a, B>
+where
+    B: BufferType,
+{
+    buffer: ::std::mem::ManuallyDrop<Buffer<B>>,
+    _owner: ::std::marker::PhantomData<&'a Buffers<B>>,
+}
+
+impl<'a, B> ::std::ops::Deref for BufferSlot<'a, B>
+where
+    B: BufferType,
+{
+    type Target = Buffer<B>;
+
+    fn deref(&self) -> &Buffer<B> {
+        &self.buffer
+    }
+}
+
+impl<'a, B> Drop for BufferSlot<'a, B>
+where
+    B: BufferType,
+{
+    fn drop(&mut self) {
+        // The buffer itself belongs to `Buffers`, only the context handle
+        // cloned for the slot has to go
+        unsafe {
+            ::std::ptr::drop_in_place(&mut self.buffer.gl);
+        }
+    }
+}
+
+/// Buffer that only holds elements of `T` and remembers how many it holds
+pub struct TypedBuffer<B, T>
+where
+    B: BufferType,
+{
+    buffer: Buffer<B>,
+    len: usize,
+    capacity: usize,
+    _marker: ::std::marker::PhantomData<T>,
+}
+
+/// Array buffer of `V` vertices
+pub type VertexBuffer<V> = TypedBuffer<BufferTypeArray, V>;
+
+/// Element array buffer of `I` indices
+pub type IndexBuffer<I> = TypedBuffer<BufferTypeElementArray, I>;
+
+impl<B, T> TypedBuffer<B, T>
+where
+    B: BufferType,
+{
+    pub fn new(gl: &gl::Gl) -> TypedBuffer<B, T> {
+        TypedBuffer {
+            buffer: Buffer::new(gl),
+            len: 0,
+            capacity: 0,
+            _marker: ::std::marker::PhantomData,
+        }
+    }
+
+    pub fn bind(&self) {
+        self.buffer.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.buffer.unbind();
+    }
+
+    /// Number of elements uploaded so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of elements the allocated storage can hold
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The untyped buffer underneath
+    pub fn buffer(&self) -> &Buffer<B> {
+        &self.buffer
+    }
+
+    pub fn static_draw_data(&mut self, data: &[T]) {
+        self.buffer.static_draw_data(data);
+        self.set_len(data.len());
+    }
+
+    pub fn dynamic_draw_data(&mut self, data: &[T]) {
+        self.buffer.dynamic_draw_data(data);
+        self.set_len(data.len());
+    }
+
+    pub fn stream_draw_data(&mut self, data: &[T]) {
+        self.buffer.stream_draw_data(data);
+        self.set_len(data.len());
+    }
+
+    /// Allocates room for `capacity` elements, leaving the buffer empty
+    pub fn reserve(&mut self, capacity: usize, usage: gl::types::GLenum) {
+        self.buffer.reserve::<T>(capacity, usage);
+        self.len = 0;
+        self.capacity = capacity;
+    }
+
+    /// Overwrites elements starting at `offset`, growing `len()`
+    /// if the data goes past the current end
+    pub fn sub_data(&mut self, offset: usize, data: &[T]) {
+        assert!(
+            offset + data.len() <= self.capacity,
+            "Writing elements {}..{} past the capacity {} of the buffer",
+            offset,
+            offset + data.len(),
+            self.capacity
+        );
+
+        self.buffer.sub_data(offset, data);
+        self.len = self.len.max(offset + data.len());
+    }
+
+    fn set_len(&mut self, len: usize) {
+        self.len = len;
+        self.capacity = len;
+    }
+}
+
 pub struct VertexArray {
     gl: gl::Gl,
     vao: gl::types::GLuint,
+    // Edit with the `VertexArray*` direct state access calls instead of binding
+    dsa: bool,
+    // Vertex types whose layouts were set up, e.g. a per-vertex and a
+    // per-instance one, checked against draws in debug builds
+    #[cfg(debug_assertions)]
+    vertex_types: ::std::cell::RefCell<Vec<(::std::any::TypeId, &'static str)>>,
 }
 
 impl VertexArray {
     pub fn new(gl: &gl::Gl) -> Self {
+        let dsa = capabilities::has_direct_state_access(gl);
+
         let mut vao: gl::types::GLuint = 0;
         unsafe {
-            gl.GenVertexArrays(1, &mut vao);
+            if dsa {
+                gl.CreateVertexArrays(1, &mut vao);
+            } else {
+                gl.GenVertexArrays(1, &mut vao);
+            }
         }
 
         VertexArray {
             gl: gl.clone(),
             vao,
+            dsa,
+            #[cfg(debug_assertions)]
+            vertex_types: ::std::cell::RefCell::new(Vec::new()),
         }
     }
 
@@ -115,6 +910,134 @@ impl VertexArray {
             self.gl.BindVertexArray(0);
         }
     }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.vao
+    }
+
+    /// Describes the layout of `V`, read from `vbo`, at the locations declared
+    /// by the vertex struct. Binds both objects unless direct state access
+    /// is available.
+    pub fn set_vertex_buffer<V>(&self, vbo: &VertexBuffer<V>)
+    where
+        V: VertexAttribPointers + 'static,
+    {
+        if self.dsa {
+            V::vertex_array_attribs(&self.gl, self.vao, vbo.buffer().id());
+        } else {
+            self.bind();
+            vbo.bind();
+            V::vertex_attrib_pointers(&self.gl);
+            vbo.unbind();
+            self.unbind();
+        }
+
+        #[cfg(debug_assertions)]
+        {
+            let mut vertex_types = self.vertex_types.borrow_mut();
+            let type_id = ::std::any::TypeId::of::<V>();
+            if !vertex_types.iter().any(|&(id, _)| id == type_id) {
+                vertex_types.push((type_id, ::std::any::type_name::<V>()));
+            }
+        }
+    }
+
+    /// Makes `ibo` the source of indices for `DrawElements*` calls
+    /// with this vertex array
+    pub fn set_index_buffer<I>(&self, ibo: &IndexBuffer<I>) {
+        if self.dsa {
+            unsafe {
+                self.gl.VertexArrayElementBuffer(self.vao, ibo.buffer().id());
+            }
+        } else {
+            // The element array binding is part of the VAO state,
+            // so it has to stay bound until the VAO is unbound
+            self.bind();
+            ibo.bind();
+            self.unbind();
+            ibo.unbind();
+        }
+    }
+
+    /// Binds the vertex array and draws every vertex in `vbo`
+    pub fn draw_arrays<V>(&self, mode: gl::types::GLenum, vbo: &VertexBuffer<V>)
+    where
+        V: 'static,
+    {
+        self.check_vertex_type::<V>();
+
+        self.bind();
+        unsafe {
+            self.gl.DrawArrays(
+                mode,                            // mode
+                0,                               // Starting index in the enabled arrays
+                vbo.len() as gl::types::GLsizei, // Number of vertices to be rendered
+            );
+        }
+    }
+
+    /// Panics in debug builds when no attribute layout was set up for `V`,
+    /// only for other vertex types
+    fn check_vertex_type<V: 'static>(&self) {
+        #[cfg(debug_assertions)]
+        {
+            let vertex_types = self.vertex_types.borrow();
+            let type_id = ::std::any::TypeId::of::<V>();
+            assert!(
+                vertex_types.is_empty() || vertex_types.iter().any(|&(id, _)| id == type_id),
+                "Vertex array layout was set up for {} but is drawn with a buffer of {}",
+                vertex_types
+                    .iter()
+                    .map(|&(_, name)| name)
+                    .collect::<Vec<&str>>()
+                    .join(", "),
+                ::std::any::type_name::<V>()
+            );
+        }
+    }
+
+    /// Binds the vertex array and draws `instance_count` instances of
+    /// `count` vertices, starting from vertex `first`
+    pub fn draw_arrays_instanced(
+        &self,
+        mode: gl::types::GLenum,
+        first: usize,
+        count: usize,
+        instance_count: usize,
+    ) {
+        self.bind();
+        unsafe {
+            self.gl.DrawArraysInstanced(
+                mode,                                   // mode
+                first as gl::types::GLint,              // Starting index in the enabled arrays
+                count as gl::types::GLsizei,            // Number of vertices per instance
+                instance_count as gl::types::GLsizei,   // Number of instances
+            );
+        }
+    }
+
+    /// Binds the vertex array and draws `instance_count` instances of `count`
+    /// indices of type `index_type`, read from the bound element array buffer
+    /// starting at byte `offset`
+    pub fn draw_elements_instanced(
+        &self,
+        mode: gl::types::GLenum,
+        count: usize,
+        index_type: gl::types::GLenum,
+        offset: usize,
+        instance_count: usize,
+    ) {
+        self.bind();
+        unsafe {
+            self.gl.DrawElementsInstanced(
+                mode,                                   // mode
+                count as gl::types::GLsizei,            // Number of indices per instance
+                index_type,                             // Type of the indices
+                offset as *const gl::types::GLvoid,     // Offset of the first index
+                instance_count as gl::types::GLsizei,   // Number of instances
+            );
+        }
+    }
 }
 
 impl Drop for VertexArray {
@@ -0,0 +1,74 @@
+use gl;
+use std::cell::Cell;
+use std::ffi::CStr;
+
+thread_local! {
+    // A GL context is only ever current on the thread that created it
+    static DIRECT_STATE_ACCESS: Cell<Option<bool>> = const { Cell::new(None) };
+    static SPIRV: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// Version of the current context as (major, minor)
+pub fn version(gl: &gl::Gl) -> (i32, i32) {
+    let mut major: gl::types::GLint = 0;
+    let mut minor: gl::types::GLint = 0;
+    unsafe {
+        gl.GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl.GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    (major, minor)
+}
+
+/// `GetString` value such as `gl::VENDOR`, `gl::RENDERER` or `gl::VERSION`
+pub fn string(gl: &gl::Gl, name: gl::types::GLenum) -> String {
+    let value = unsafe { gl.GetString(name) };
+    if value.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(value as *const _) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Whether the current context advertises extension `name`, e.g. `GL_ARB_gl_spirv`
+pub fn has_extension(gl: &gl::Gl, name: &str) -> bool {
+    let mut count: gl::types::GLint = 0;
+    unsafe {
+        gl.GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+    }
+
+    (0..count).any(|i| {
+        let extension = unsafe { gl.GetStringi(gl::EXTENSIONS, i as gl::types::GLuint) };
+        !extension.is_null()
+            && unsafe { CStr::from_ptr(extension as *const _) }.to_bytes() == name.as_bytes()
+    })
+}
+
+/// Whether buffers and vertex arrays can be edited without binding them,
+/// with GL 4.5 or `GL_ARB_direct_state_access`. Queried once per thread.
+pub fn has_direct_state_access(gl: &gl::Gl) -> bool {
+    DIRECT_STATE_ACCESS.with(|cached| match cached.get() {
+        Some(dsa) => dsa,
+        None => {
+            let dsa = gl.CreateBuffers.is_loaded()
+                && (version(gl) >= (4, 5) || has_extension(gl, "GL_ARB_direct_state_access"));
+            cached.set(Some(dsa));
+            dsa
+        }
+    })
+}
+
+/// Whether shaders can be loaded from SPIR-V binaries,
+/// with GL 4.6 or `GL_ARB_gl_spirv`. Queried once per thread.
+pub fn has_spirv(gl: &gl::Gl) -> bool {
+    SPIRV.with(|cached| match cached.get() {
+        Some(spirv) => spirv,
+        None => {
+            // Loaded from `glSpecializeShaderARB` when the core entry point is missing
+            let spirv = gl.SpecializeShader.is_loaded()
+                && (version(gl) >= (4, 6) || has_extension(gl, "GL_ARB_gl_spirv"));
+            cached.set(Some(spirv));
+            spirv
+        }
+    })
+}
@@ -0,0 +1,119 @@
+use crate::render_gl::buffer::DispatchIndirectBuffer;
+use crate::render_gl::data::Pod;
+use crate::render_gl::shader::{Error, Program, Shader};
+use crate::resources::Resources;
+use gl;
+
+/// Work group counts read by `ComputeProgram::dispatch_indirect`,
+/// laid out like `DispatchIndirectCommand`
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct DispatchIndirectCommand {
+    pub num_groups_x: gl::types::GLuint,
+    pub num_groups_y: gl::types::GLuint,
+    pub num_groups_z: gl::types::GLuint,
+}
+
+unsafe impl Pod for DispatchIndirectCommand {}
+
+/// Program made of a single compute shader
+pub struct ComputeProgram {
+    gl: gl::Gl,
+    program: Program,
+}
+
+impl ComputeProgram {
+    /// Loads and links resource `{name}.comp`
+    pub fn from_res(gl: &gl::Gl, res: &Resources, name: &str) -> Result<ComputeProgram, Error> {
+        ComputeProgram::from_res_with_defines(gl, res, name, &[])
+    }
+
+    /// Like `from_res`, with `#define NAME VALUE` added to the shader
+    pub fn from_res_with_defines(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<ComputeProgram, Error> {
+        let shader = Shader::from_res_with_defines(gl, res, &format!("{}.comp", name), defines)?;
+
+        let program = Program::from_shaders(gl, &[shader]).map_err(|log| Error::link(name, &log))?;
+
+        Ok(ComputeProgram {
+            gl: gl.clone(),
+            program,
+        })
+    }
+
+    /// The linked program, for setting uniforms and reflection
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// `layout (local_size_x, local_size_y, local_size_z)` of the shader
+    pub fn work_group_size(&self) -> [gl::types::GLint; 3] {
+        let mut size: [gl::types::GLint; 3] = [0; 3];
+        unsafe {
+            self.gl.GetProgramiv(
+                self.program.id(),
+                gl::COMPUTE_WORK_GROUP_SIZE,
+                size.as_mut_ptr(),
+            );
+        }
+        size
+    }
+
+    /// Runs `x * y * z` work groups
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) {
+        self.program.set_used();
+        unsafe {
+            self.gl.DispatchCompute(x, y, z);
+        }
+    }
+
+    /// Runs the number of work groups stored in `buffer` as a
+    /// `DispatchIndirectCommand` at byte `offset`, e.g. written
+    /// by an earlier dispatch
+    pub fn dispatch_indirect(&self, buffer: &DispatchIndirectBuffer, offset: usize) {
+        self.program.set_used();
+        buffer.bind();
+        unsafe {
+            self.gl.DispatchComputeIndirect(offset as gl::types::GLintptr);
+        }
+        buffer.unbind();
+    }
+}
+
+/// Makes shader writes before this call visible to the accesses selected
+/// by `barriers`, e.g. `gl::SHADER_STORAGE_BARRIER_BIT`
+pub fn memory_barrier(gl: &gl::Gl, barriers: gl::types::GLbitfield) {
+    unsafe {
+        gl.MemoryBarrier(barriers);
+    }
+}
+
+/// Before reading a storage buffer a previous dispatch wrote to
+pub fn shader_storage_barrier(gl: &gl::Gl) {
+    memory_barrier(gl, gl::SHADER_STORAGE_BARRIER_BIT);
+}
+
+/// Before `imageLoad`ing what a previous dispatch `imageStore`d
+pub fn shader_image_access_barrier(gl: &gl::Gl) {
+    memory_barrier(gl, gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+}
+
+/// Before drawing from vertices or indices a previous dispatch wrote
+pub fn vertex_data_barrier(gl: &gl::Gl) {
+    memory_barrier(gl, gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT | gl::ELEMENT_ARRAY_BARRIER_BIT);
+}
+
+/// Before a `Draw*Indirect` or `DispatchComputeIndirect` reading
+/// commands a previous dispatch wrote
+pub fn command_barrier(gl: &gl::Gl) {
+    memory_barrier(gl, gl::COMMAND_BARRIER_BIT);
+}
+
+/// Before reading back or mapping a buffer a previous dispatch wrote
+pub fn buffer_update_barrier(gl: &gl::Gl) {
+    memory_barrier(gl, gl::BUFFER_UPDATE_BARRIER_BIT);
+}
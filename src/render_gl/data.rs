@@ -1,21 +1,85 @@
 use gl;
+use nalgebra as na;
 
-#[allow(non_camel_case_types)]
-#[derive(Copy, Clone, Debug)]
-#[repr(C, packed)]
-pub struct f32_f32_f32 {
-    pub d0: f32,
-    pub d1: f32,
-    pub d2: f32,
+/// A vertex struct whose fields can be described to OpenGL,
+/// usually implemented with `#[derive(VertexAttribPointers)]`.
+///
+/// The derive refers to this module as `crate::render_gl::data`, which only
+/// resolves inside this crate; elsewhere add `#[vertex(crate = "path")]` to
+/// the struct with the path `render_gl` can be reached from.
+pub trait VertexAttribPointers {
+    /// Enables and describes every attribute of the vertex for the
+    /// currently bound vertex array and array buffer
+    fn vertex_attrib_pointers(gl: &gl::Gl);
+
+    /// Direct state access counterpart of `vertex_attrib_pointers`,
+    /// describes the attributes read from `vbo` to `vao` without binding either
+    fn vertex_array_attribs(gl: &gl::Gl, vao: gl::types::GLuint, vbo: gl::types::GLuint);
+
+    /// Every attribute of the vertex, to check against the program inputs
+    fn vertex_attribs() -> Vec<VertexAttribInfo>;
 }
 
-impl f32_f32_f32 {
-    /// Convenience constructor
-    pub fn new(d0: f32, d1: f32, d2: f32) -> f32_f32_f32 {
-        f32_f32_f32 { d0, d1, d2 }
-    }
+/// Description of a single vertex struct field
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexAttribInfo {
+    /// Field name, or index for tuple structs
+    pub name: &'static str,
+    /// `layout (location = N)` the attribute is read from
+    pub location: usize,
+    /// GLSL type the shader input should be declared as (`gl::FLOAT_VEC3`, ...)
+    pub shader_type: gl::types::GLenum,
+}
+
+/// Plain data that mapped buffer memory can be viewed as.
+///
+/// # Safety
+///
+/// Every bit pattern of `size_of::<Self>()` bytes must be a valid value, and
+/// the type must not contain padding or pointers, e.g. `#[repr(C)]` structs
+/// of the types this is implemented for.
+pub unsafe trait Pod: Copy {}
+
+macro_rules! pod {
+    ($($t:ty),+) => {
+        $(unsafe impl Pod for $t {})+
+    };
+}
 
-    pub unsafe fn vertex_attrib_pointer(
+pod!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// A type that can be used as a field of a `#[derive(VertexAttribPointers)]` struct.
+///
+/// Implement this for your own types to describe how OpenGL should read them
+/// from a vertex buffer.
+pub trait VertexAttribute {
+    /// Number of components per generic vertex attribute (1 - 4)
+    const COMPONENTS: gl::types::GLint;
+
+    /// Data type of each component (`gl::FLOAT`, `gl::UNSIGNED_BYTE`, ...)
+    const GL_TYPE: gl::types::GLenum;
+
+    /// Whether integer data is normalized when converted to float
+    const NORMALIZED: gl::types::GLboolean = gl::FALSE;
+
+    /// Number of consecutive locations the attribute takes up,
+    /// 2 for `dvec3` and `dvec4`
+    const LOCATIONS: usize = 1;
+
+    /// GLSL type the attribute is seen as in the shader. Anything read with
+    /// `VertexAttribPointer` becomes a `float`/`vecN`.
+    const SHADER_TYPE: gl::types::GLenum = float_shader_type(Self::COMPONENTS);
+
+    /// Enables and describes the attribute at `location` for the currently
+    /// bound vertex array and array buffer.
+    ///
+    /// # Safety
+    ///
+    /// A vertex array and an array buffer must be bound, and `stride`/`offset`
+    /// must describe data actually stored in that buffer.
+    unsafe fn vertex_attrib_pointer(
         gl: &gl::Gl,
         location: usize,
         stride: usize,
@@ -24,18 +88,651 @@ impl f32_f32_f32 {
         gl.EnableVertexAttribArray(location as gl::types::GLuint);
         gl.VertexAttribPointer(
             location as gl::types::GLuint,
-            3,                                  // num components per generic vertex attribute
-            gl::FLOAT,                          // Data type
-            gl::FALSE,                          // Normalized (int to float conversion)
+            Self::COMPONENTS,                   // num components per generic vertex attribute
+            Self::GL_TYPE,                      // Data type
+            Self::NORMALIZED,                   // Normalized (int to float conversion)
             stride as gl::types::GLint,         // Stride
             offset as *const gl::types::GLvoid, // offset of the first entry of this component
         );
     }
+
+    /// Direct state access counterpart of `vertex_attrib_pointer`. Every
+    /// attribute gets its own buffer binding point (same index as its
+    /// location), so divisors can be set per attribute.
+    ///
+    /// # Safety
+    ///
+    /// `vao` and `vbo` must be objects created by `CreateVertexArrays` and
+    /// `CreateBuffers`, and `stride`/`offset` must describe data actually
+    /// stored in `vbo`.
+    unsafe fn vertex_array_attrib(
+        gl: &gl::Gl,
+        vao: gl::types::GLuint,
+        vbo: gl::types::GLuint,
+        location: usize,
+        stride: usize,
+        offset: usize,
+    ) {
+        let location = location as gl::types::GLuint;
+
+        gl.EnableVertexArrayAttrib(vao, location);
+        gl.VertexArrayVertexBuffer(
+            vao,
+            location,                       // binding index
+            vbo,
+            offset as gl::types::GLintptr,  // offset of the first entry of this component
+            stride as gl::types::GLsizei,   // Stride
+        );
+        Self::vertex_array_attrib_format(gl, vao, location);
+        gl.VertexArrayAttribBinding(vao, location, location);
+    }
+
+    /// Describes the format of the attribute at `location` of `vao`
+    ///
+    /// # Safety
+    ///
+    /// `vao` must be an object created by `CreateVertexArrays`.
+    unsafe fn vertex_array_attrib_format(
+        gl: &gl::Gl,
+        vao: gl::types::GLuint,
+        location: gl::types::GLuint,
+    ) {
+        gl.VertexArrayAttribFormat(
+            vao,
+            location,
+            Self::COMPONENTS,   // num components per generic vertex attribute
+            Self::GL_TYPE,      // Data type
+            Self::NORMALIZED,   // Normalized (int to float conversion)
+            0,                  // relative offset, the binding already points at the component
+        );
+    }
+}
+
+/// Whether `count_a` locations from `a` and `count_b` locations from `b` share one
+pub const fn locations_overlap(a: usize, count_a: usize, b: usize, count_b: usize) -> bool {
+    a < b + count_b && b < a + count_a
+}
+
+/// `float`, `vec2`, `vec3` or `vec4`
+pub const fn float_shader_type(components: gl::types::GLint) -> gl::types::GLenum {
+    match components {
+        1 => gl::FLOAT,
+        2 => gl::FLOAT_VEC2,
+        3 => gl::FLOAT_VEC3,
+        _ => gl::FLOAT_VEC4,
+    }
+}
+
+/// `int`/`ivecN` for signed `gl_type`s, `uint`/`uvecN` for unsigned ones
+pub const fn integer_shader_type(
+    gl_type: gl::types::GLenum,
+    components: gl::types::GLint,
+) -> gl::types::GLenum {
+    let signed = matches!(gl_type, gl::BYTE | gl::SHORT | gl::INT);
+    match (signed, components) {
+        (true, 1) => gl::INT,
+        (true, 2) => gl::INT_VEC2,
+        (true, 3) => gl::INT_VEC3,
+        (true, _) => gl::INT_VEC4,
+        (false, 1) => gl::UNSIGNED_INT,
+        (false, 2) => gl::UNSIGNED_INT_VEC2,
+        (false, 3) => gl::UNSIGNED_INT_VEC3,
+        (false, _) => gl::UNSIGNED_INT_VEC4,
+    }
+}
+
+/// `double` or `dvecN`
+pub const fn double_shader_type(components: gl::types::GLint) -> gl::types::GLenum {
+    match components {
+        1 => gl::DOUBLE,
+        2 => gl::DOUBLE_VEC2,
+        3 => gl::DOUBLE_VEC3,
+        _ => gl::DOUBLE_VEC4,
+    }
+}
+
+/// Declares a `#[repr(C, packed)]` vertex attribute type with a convenience
+/// constructor, a `From` conversion from the matching tuple and a
+/// `VertexAttribute` impl.
+///
+/// * `float` - read with `VertexAttribPointer`, optionally normalized
+/// * `integer` - read with `VertexAttribIPointer`, stays an integer in the shader
+/// * `double` - read with `VertexAttribLPointer`, stays a double in the shader
+macro_rules! vertex_attribute_type {
+    (float $name:ident { $($field:ident: $t:ty),+ }, $gl_type:expr, $normalized:expr) => {
+        vertex_attribute_type!(@struct $name { $($field: $t),+ });
+
+        impl VertexAttribute for $name {
+            const COMPONENTS: gl::types::GLint = vertex_attribute_type!(@count $($field)+);
+            const GL_TYPE: gl::types::GLenum = $gl_type;
+            const NORMALIZED: gl::types::GLboolean = $normalized;
+        }
+    };
+
+    (integer $name:ident { $($field:ident: $t:ty),+ }, $gl_type:expr) => {
+        vertex_attribute_type!(@struct $name { $($field: $t),+ });
+
+        impl VertexAttribute for $name {
+            const COMPONENTS: gl::types::GLint = vertex_attribute_type!(@count $($field)+);
+            const GL_TYPE: gl::types::GLenum = $gl_type;
+            const SHADER_TYPE: gl::types::GLenum = integer_shader_type(Self::GL_TYPE, Self::COMPONENTS);
+
+            unsafe fn vertex_attrib_pointer(
+                gl: &gl::Gl,
+                location: usize,
+                stride: usize,
+                offset: usize,
+            ) {
+                gl.EnableVertexAttribArray(location as gl::types::GLuint);
+                gl.VertexAttribIPointer(
+                    location as gl::types::GLuint,
+                    Self::COMPONENTS,                   // num components per generic vertex attribute
+                    Self::GL_TYPE,                      // Data type
+                    stride as gl::types::GLint,         // Stride
+                    offset as *const gl::types::GLvoid, // offset of the first entry of this component
+                );
+            }
+
+            unsafe fn vertex_array_attrib_format(
+                gl: &gl::Gl,
+                vao: gl::types::GLuint,
+                location: gl::types::GLuint,
+            ) {
+                gl.VertexArrayAttribIFormat(vao, location, Self::COMPONENTS, Self::GL_TYPE, 0);
+            }
+        }
+    };
+
+    (double $name:ident { $($field:ident: $t:ty),+ }, $gl_type:expr) => {
+        vertex_attribute_type!(@struct $name { $($field: $t),+ });
+
+        impl VertexAttribute for $name {
+            const COMPONENTS: gl::types::GLint = vertex_attribute_type!(@count $($field)+);
+            const GL_TYPE: gl::types::GLenum = $gl_type;
+            const SHADER_TYPE: gl::types::GLenum = double_shader_type(Self::COMPONENTS);
+            const LOCATIONS: usize = if Self::COMPONENTS > 2 { 2 } else { 1 };
+
+            unsafe fn vertex_attrib_pointer(
+                gl: &gl::Gl,
+                location: usize,
+                stride: usize,
+                offset: usize,
+            ) {
+                gl.EnableVertexAttribArray(location as gl::types::GLuint);
+                gl.VertexAttribLPointer(
+                    location as gl::types::GLuint,
+                    Self::COMPONENTS,                   // num components per generic vertex attribute
+                    Self::GL_TYPE,                      // Data type
+                    stride as gl::types::GLint,         // Stride
+                    offset as *const gl::types::GLvoid, // offset of the first entry of this component
+                );
+            }
+
+            unsafe fn vertex_array_attrib_format(
+                gl: &gl::Gl,
+                vao: gl::types::GLuint,
+                location: gl::types::GLuint,
+            ) {
+                gl.VertexArrayAttribLFormat(vao, location, Self::COMPONENTS, Self::GL_TYPE, 0);
+            }
+        }
+    };
+
+    (@struct $name:ident { $($field:ident: $t:ty),+ }) => {
+        #[allow(non_camel_case_types)]
+        #[derive(Copy, Clone, Debug)]
+        #[repr(C, packed)]
+        pub struct $name {
+            $(pub $field: $t),+
+        }
+
+        unsafe impl Pod for $name {}
+
+        impl $name {
+            /// Convenience constructor
+            pub fn new($($field: $t),+) -> $name {
+                $name { $($field),+ }
+            }
+        }
+
+        /// Create from tuple (or a single value for one component types)
+        #[allow(unused_parens)]
+        impl From<($($t),+)> for $name {
+            fn from(other: ($($t),+)) -> Self {
+                let ($($field),+) = other;
+                $name::new($($field),+)
+            }
+        }
+    };
+
+    (@count $($field:ident)+) => {
+        0 $(+ vertex_attribute_type!(@one $field))+
+    };
+
+    (@one $field:ident) => {
+        1
+    };
+}
+
+// Floating point attributes
+
+vertex_attribute_type!(float f32_ { d0: f32 }, gl::FLOAT, gl::FALSE);
+vertex_attribute_type!(float f32_f32 { d0: f32, d1: f32 }, gl::FLOAT, gl::FALSE);
+vertex_attribute_type!(float f32_f32_f32 { d0: f32, d1: f32, d2: f32 }, gl::FLOAT, gl::FALSE);
+vertex_attribute_type!(float f32_f32_f32_f32 { d0: f32, d1: f32, d2: f32, d3: f32 }, gl::FLOAT, gl::FALSE);
+
+// Double precision attributes, `dvec` inputs in the shader
+
+vertex_attribute_type!(double f64_ { d0: f64 }, gl::DOUBLE);
+vertex_attribute_type!(double f64_f64 { d0: f64, d1: f64 }, gl::DOUBLE);
+vertex_attribute_type!(double f64_f64_f64 { d0: f64, d1: f64, d2: f64 }, gl::DOUBLE);
+vertex_attribute_type!(double f64_f64_f64_f64 { d0: f64, d1: f64, d2: f64, d3: f64 }, gl::DOUBLE);
+
+// i8 attributes, `_float` variants are normalized to [-1, 1] floats in the shader
+
+vertex_attribute_type!(integer i8_ { d0: i8 }, gl::BYTE);
+vertex_attribute_type!(integer i8_i8 { d0: i8, d1: i8 }, gl::BYTE);
+vertex_attribute_type!(integer i8_i8_i8 { d0: i8, d1: i8, d2: i8 }, gl::BYTE);
+vertex_attribute_type!(integer i8_i8_i8_i8 { d0: i8, d1: i8, d2: i8, d3: i8 }, gl::BYTE);
+vertex_attribute_type!(float i8_float { d0: i8 }, gl::BYTE, gl::TRUE);
+vertex_attribute_type!(float i8_i8_float { d0: i8, d1: i8 }, gl::BYTE, gl::TRUE);
+vertex_attribute_type!(float i8_i8_i8_float { d0: i8, d1: i8, d2: i8 }, gl::BYTE, gl::TRUE);
+vertex_attribute_type!(float i8_i8_i8_i8_float { d0: i8, d1: i8, d2: i8, d3: i8 }, gl::BYTE, gl::TRUE);
+
+// u8 attributes, `_float` variants are normalized to [0, 1] floats in the shader
+
+vertex_attribute_type!(integer u8_ { d0: u8 }, gl::UNSIGNED_BYTE);
+vertex_attribute_type!(integer u8_u8 { d0: u8, d1: u8 }, gl::UNSIGNED_BYTE);
+vertex_attribute_type!(integer u8_u8_u8 { d0: u8, d1: u8, d2: u8 }, gl::UNSIGNED_BYTE);
+vertex_attribute_type!(integer u8_u8_u8_u8 { d0: u8, d1: u8, d2: u8, d3: u8 }, gl::UNSIGNED_BYTE);
+vertex_attribute_type!(float u8_float { d0: u8 }, gl::UNSIGNED_BYTE, gl::TRUE);
+vertex_attribute_type!(float u8_u8_float { d0: u8, d1: u8 }, gl::UNSIGNED_BYTE, gl::TRUE);
+vertex_attribute_type!(float u8_u8_u8_float { d0: u8, d1: u8, d2: u8 }, gl::UNSIGNED_BYTE, gl::TRUE);
+vertex_attribute_type!(float u8_u8_u8_u8_float { d0: u8, d1: u8, d2: u8, d3: u8 }, gl::UNSIGNED_BYTE, gl::TRUE);
+
+// i16 attributes, `_float` variants are normalized to [-1, 1] floats in the shader
+
+vertex_attribute_type!(integer i16_ { d0: i16 }, gl::SHORT);
+vertex_attribute_type!(integer i16_i16 { d0: i16, d1: i16 }, gl::SHORT);
+vertex_attribute_type!(integer i16_i16_i16 { d0: i16, d1: i16, d2: i16 }, gl::SHORT);
+vertex_attribute_type!(integer i16_i16_i16_i16 { d0: i16, d1: i16, d2: i16, d3: i16 }, gl::SHORT);
+vertex_attribute_type!(float i16_float { d0: i16 }, gl::SHORT, gl::TRUE);
+vertex_attribute_type!(float i16_i16_float { d0: i16, d1: i16 }, gl::SHORT, gl::TRUE);
+vertex_attribute_type!(float i16_i16_i16_float { d0: i16, d1: i16, d2: i16 }, gl::SHORT, gl::TRUE);
+vertex_attribute_type!(float i16_i16_i16_i16_float { d0: i16, d1: i16, d2: i16, d3: i16 }, gl::SHORT, gl::TRUE);
+
+// u16 attributes, `_float` variants are normalized to [0, 1] floats in the shader
+
+vertex_attribute_type!(integer u16_ { d0: u16 }, gl::UNSIGNED_SHORT);
+vertex_attribute_type!(integer u16_u16 { d0: u16, d1: u16 }, gl::UNSIGNED_SHORT);
+vertex_attribute_type!(integer u16_u16_u16 { d0: u16, d1: u16, d2: u16 }, gl::UNSIGNED_SHORT);
+vertex_attribute_type!(integer u16_u16_u16_u16 { d0: u16, d1: u16, d2: u16, d3: u16 }, gl::UNSIGNED_SHORT);
+vertex_attribute_type!(float u16_float { d0: u16 }, gl::UNSIGNED_SHORT, gl::TRUE);
+vertex_attribute_type!(float u16_u16_float { d0: u16, d1: u16 }, gl::UNSIGNED_SHORT, gl::TRUE);
+vertex_attribute_type!(float u16_u16_u16_float { d0: u16, d1: u16, d2: u16 }, gl::UNSIGNED_SHORT, gl::TRUE);
+vertex_attribute_type!(float u16_u16_u16_u16_float { d0: u16, d1: u16, d2: u16, d3: u16 }, gl::UNSIGNED_SHORT, gl::TRUE);
+
+// i32 attributes, `_float` variants are normalized to [-1, 1] floats in the shader
+
+vertex_attribute_type!(integer i32_ { d0: i32 }, gl::INT);
+vertex_attribute_type!(integer i32_i32 { d0: i32, d1: i32 }, gl::INT);
+vertex_attribute_type!(integer i32_i32_i32 { d0: i32, d1: i32, d2: i32 }, gl::INT);
+vertex_attribute_type!(integer i32_i32_i32_i32 { d0: i32, d1: i32, d2: i32, d3: i32 }, gl::INT);
+vertex_attribute_type!(float i32_float { d0: i32 }, gl::INT, gl::TRUE);
+vertex_attribute_type!(float i32_i32_float { d0: i32, d1: i32 }, gl::INT, gl::TRUE);
+vertex_attribute_type!(float i32_i32_i32_float { d0: i32, d1: i32, d2: i32 }, gl::INT, gl::TRUE);
+vertex_attribute_type!(float i32_i32_i32_i32_float { d0: i32, d1: i32, d2: i32, d3: i32 }, gl::INT, gl::TRUE);
+
+// u32 attributes, `_float` variants are normalized to [0, 1] floats in the shader
+
+vertex_attribute_type!(integer u32_ { d0: u32 }, gl::UNSIGNED_INT);
+vertex_attribute_type!(integer u32_u32 { d0: u32, d1: u32 }, gl::UNSIGNED_INT);
+vertex_attribute_type!(integer u32_u32_u32 { d0: u32, d1: u32, d2: u32 }, gl::UNSIGNED_INT);
+vertex_attribute_type!(integer u32_u32_u32_u32 { d0: u32, d1: u32, d2: u32, d3: u32 }, gl::UNSIGNED_INT);
+vertex_attribute_type!(float u32_float { d0: u32 }, gl::UNSIGNED_INT, gl::TRUE);
+vertex_attribute_type!(float u32_u32_float { d0: u32, d1: u32 }, gl::UNSIGNED_INT, gl::TRUE);
+vertex_attribute_type!(float u32_u32_u32_float { d0: u32, d1: u32, d2: u32 }, gl::UNSIGNED_INT, gl::TRUE);
+vertex_attribute_type!(float u32_u32_u32_u32_float { d0: u32, d1: u32, d2: u32, d3: u32 }, gl::UNSIGNED_INT, gl::TRUE);
+
+// Packed 2:10:10:10 attributes, `x` lives in the lowest 10 bits and `w` in the top 2
+
+/// Four unsigned components packed into a single `u32`,
+/// read as unnormalized floats in the shader
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug)]
+#[repr(C, packed)]
+pub struct u2_u10_u10_u10_rev {
+    pub inner: u32,
+}
+
+unsafe impl Pod for u2_u10_u10_u10_rev {}
+
+impl u2_u10_u10_u10_rev {
+    /// Convenience constructor, out of range bits are discarded
+    pub fn new(x: u16, y: u16, z: u16, w: u8) -> u2_u10_u10_u10_rev {
+        u2_u10_u10_u10_rev {
+            inner: pack_2_10_10_10_rev(x as u32, y as u32, z as u32, w as u32),
+        }
+    }
+}
+
+/// Create from (x, y, z, w) tuple
+impl From<(u16, u16, u16, u8)> for u2_u10_u10_u10_rev {
+    fn from(other: (u16, u16, u16, u8)) -> Self {
+        u2_u10_u10_u10_rev::new(other.0, other.1, other.2, other.3)
+    }
+}
+
+impl VertexAttribute for u2_u10_u10_u10_rev {
+    const COMPONENTS: gl::types::GLint = 4;
+    const GL_TYPE: gl::types::GLenum = gl::UNSIGNED_INT_2_10_10_10_REV;
+}
+
+/// Four unsigned components packed into a single `u32`,
+/// normalized to [0, 1] floats in the shader
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug)]
+#[repr(C, packed)]
+pub struct u2_u10_u10_u10_rev_float {
+    pub inner: u32,
 }
 
-/// Create from 3-f32 tuple
-impl From<(f32, f32, f32)> for f32_f32_f32 {
-    fn from(other: (f32, f32, f32)) -> Self {
-        f32_f32_f32::new(other.0, other.1, other.2)
+unsafe impl Pod for u2_u10_u10_u10_rev_float {}
+
+impl u2_u10_u10_u10_rev_float {
+    /// Convenience constructor, components are clamped to [0, 1]
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> u2_u10_u10_u10_rev_float {
+        let quantize = |v: f32, max: u32| (v.clamp(0.0, 1.0) * max as f32).round() as u32;
+
+        u2_u10_u10_u10_rev_float {
+            inner: pack_2_10_10_10_rev(
+                quantize(x, 1023),
+                quantize(y, 1023),
+                quantize(z, 1023),
+                quantize(w, 3),
+            ),
+        }
+    }
+}
+
+/// Create from (x, y, z, w) tuple
+impl From<(f32, f32, f32, f32)> for u2_u10_u10_u10_rev_float {
+    fn from(other: (f32, f32, f32, f32)) -> Self {
+        u2_u10_u10_u10_rev_float::new(other.0, other.1, other.2, other.3)
+    }
+}
+
+impl VertexAttribute for u2_u10_u10_u10_rev_float {
+    const COMPONENTS: gl::types::GLint = 4;
+    const GL_TYPE: gl::types::GLenum = gl::UNSIGNED_INT_2_10_10_10_REV;
+    const NORMALIZED: gl::types::GLboolean = gl::TRUE;
+}
+
+fn pack_2_10_10_10_rev(x: u32, y: u32, z: u32, w: u32) -> u32 {
+    (x & 0x3ff) | ((y & 0x3ff) << 10) | ((z & 0x3ff) << 20) | ((w & 0x3) << 30)
+}
+
+// nalgebra interop
+
+/// Conversions between a vertex attribute type and the nalgebra vector and
+/// point of the same element type and dimension. The `attribute` form also
+/// lets the vector and point be used directly as vertex struct fields, read
+/// the same way as the attribute type.
+macro_rules! nalgebra_vertex_attribute {
+    (attribute $name:ident, $t:ty, $vector:ident, $point:ident { $($field:ident: $idx:tt),+ }) => {
+        nalgebra_vertex_attribute!($name, $t, $vector, $point { $($field: $idx),+ });
+
+        impl VertexAttribute for na::$vector<$t> {
+            const COMPONENTS: gl::types::GLint = <$name as VertexAttribute>::COMPONENTS;
+            const GL_TYPE: gl::types::GLenum = <$name as VertexAttribute>::GL_TYPE;
+            const NORMALIZED: gl::types::GLboolean = <$name as VertexAttribute>::NORMALIZED;
+            const SHADER_TYPE: gl::types::GLenum = <$name as VertexAttribute>::SHADER_TYPE;
+            const LOCATIONS: usize = <$name as VertexAttribute>::LOCATIONS;
+
+            unsafe fn vertex_attrib_pointer(
+                gl: &gl::Gl,
+                location: usize,
+                stride: usize,
+                offset: usize,
+            ) {
+                <$name as VertexAttribute>::vertex_attrib_pointer(gl, location, stride, offset);
+            }
+
+            unsafe fn vertex_array_attrib_format(
+                gl: &gl::Gl,
+                vao: gl::types::GLuint,
+                location: gl::types::GLuint,
+            ) {
+                <$name as VertexAttribute>::vertex_array_attrib_format(gl, vao, location);
+            }
+        }
+
+        impl VertexAttribute for na::$point<$t> {
+            const COMPONENTS: gl::types::GLint = <$name as VertexAttribute>::COMPONENTS;
+            const GL_TYPE: gl::types::GLenum = <$name as VertexAttribute>::GL_TYPE;
+            const NORMALIZED: gl::types::GLboolean = <$name as VertexAttribute>::NORMALIZED;
+            const SHADER_TYPE: gl::types::GLenum = <$name as VertexAttribute>::SHADER_TYPE;
+            const LOCATIONS: usize = <$name as VertexAttribute>::LOCATIONS;
+
+            unsafe fn vertex_attrib_pointer(
+                gl: &gl::Gl,
+                location: usize,
+                stride: usize,
+                offset: usize,
+            ) {
+                <$name as VertexAttribute>::vertex_attrib_pointer(gl, location, stride, offset);
+            }
+
+            unsafe fn vertex_array_attrib_format(
+                gl: &gl::Gl,
+                vao: gl::types::GLuint,
+                location: gl::types::GLuint,
+            ) {
+                <$name as VertexAttribute>::vertex_array_attrib_format(gl, vao, location);
+            }
+        }
+    };
+
+    ($name:ident, $t:ty, $vector:ident, $point:ident { $($field:ident: $idx:tt),+ }) => {
+        impl From<na::$vector<$t>> for $name {
+            fn from(other: na::$vector<$t>) -> Self {
+                $name::new($(other[$idx]),+)
+            }
+        }
+
+        impl From<$name> for na::$vector<$t> {
+            fn from(other: $name) -> Self {
+                na::$vector::new($(other.$field),+)
+            }
+        }
+
+        impl From<na::$point<$t>> for $name {
+            fn from(other: na::$point<$t>) -> Self {
+                $name::from(other.coords)
+            }
+        }
+
+        impl From<$name> for na::$point<$t> {
+            fn from(other: $name) -> Self {
+                na::$point::from(na::$vector::<$t>::from(other))
+            }
+        }
+    };
+}
+
+/// Conversions between a floating point attribute type and unit vectors.
+/// Converting into a unit vector normalizes the value, and unit vectors
+/// can be used directly as vertex struct fields.
+macro_rules! nalgebra_unit_vertex_attribute {
+    ($name:ident, $t:ty, $vector:ident) => {
+        impl From<na::Unit<na::$vector<$t>>> for $name {
+            fn from(other: na::Unit<na::$vector<$t>>) -> Self {
+                $name::from(other.into_inner())
+            }
+        }
+
+        impl From<$name> for na::Unit<na::$vector<$t>> {
+            fn from(other: $name) -> Self {
+                na::Unit::new_normalize(na::$vector::<$t>::from(other))
+            }
+        }
+
+        impl VertexAttribute for na::Unit<na::$vector<$t>> {
+            const COMPONENTS: gl::types::GLint = <$name as VertexAttribute>::COMPONENTS;
+            const GL_TYPE: gl::types::GLenum = <$name as VertexAttribute>::GL_TYPE;
+            const NORMALIZED: gl::types::GLboolean = <$name as VertexAttribute>::NORMALIZED;
+            const SHADER_TYPE: gl::types::GLenum = <$name as VertexAttribute>::SHADER_TYPE;
+            const LOCATIONS: usize = <$name as VertexAttribute>::LOCATIONS;
+
+            unsafe fn vertex_attrib_pointer(
+                gl: &gl::Gl,
+                location: usize,
+                stride: usize,
+                offset: usize,
+            ) {
+                <$name as VertexAttribute>::vertex_attrib_pointer(gl, location, stride, offset);
+            }
+
+            unsafe fn vertex_array_attrib_format(
+                gl: &gl::Gl,
+                vao: gl::types::GLuint,
+                location: gl::types::GLuint,
+            ) {
+                <$name as VertexAttribute>::vertex_array_attrib_format(gl, vao, location);
+            }
+        }
+    };
+}
+
+// Floating point and integer types back nalgebra fields of the same element
+// type, normalized `_float` types only convert and need to be used explicitly
+
+nalgebra_vertex_attribute!(attribute f32_f32, f32, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(attribute f32_f32_f32, f32, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(attribute f32_f32_f32_f32, f32, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+
+nalgebra_vertex_attribute!(attribute f64_f64, f64, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(attribute f64_f64_f64, f64, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(attribute f64_f64_f64_f64, f64, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+
+nalgebra_vertex_attribute!(attribute i8_i8, i8, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(attribute i8_i8_i8, i8, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(attribute i8_i8_i8_i8, i8, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+nalgebra_vertex_attribute!(i8_i8_float, i8, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(i8_i8_i8_float, i8, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(i8_i8_i8_i8_float, i8, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+
+nalgebra_vertex_attribute!(attribute u8_u8, u8, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(attribute u8_u8_u8, u8, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(attribute u8_u8_u8_u8, u8, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+nalgebra_vertex_attribute!(u8_u8_float, u8, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(u8_u8_u8_float, u8, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(u8_u8_u8_u8_float, u8, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+
+nalgebra_vertex_attribute!(attribute i16_i16, i16, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(attribute i16_i16_i16, i16, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(attribute i16_i16_i16_i16, i16, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+nalgebra_vertex_attribute!(i16_i16_float, i16, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(i16_i16_i16_float, i16, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(i16_i16_i16_i16_float, i16, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+
+nalgebra_vertex_attribute!(attribute u16_u16, u16, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(attribute u16_u16_u16, u16, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(attribute u16_u16_u16_u16, u16, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+nalgebra_vertex_attribute!(u16_u16_float, u16, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(u16_u16_u16_float, u16, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(u16_u16_u16_u16_float, u16, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+
+nalgebra_vertex_attribute!(attribute i32_i32, i32, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(attribute i32_i32_i32, i32, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(attribute i32_i32_i32_i32, i32, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+nalgebra_vertex_attribute!(i32_i32_float, i32, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(i32_i32_i32_float, i32, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(i32_i32_i32_i32_float, i32, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+
+nalgebra_vertex_attribute!(attribute u32_u32, u32, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(attribute u32_u32_u32, u32, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(attribute u32_u32_u32_u32, u32, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+nalgebra_vertex_attribute!(u32_u32_float, u32, Vector2, Point2 { d0: 0, d1: 1 });
+nalgebra_vertex_attribute!(u32_u32_u32_float, u32, Vector3, Point3 { d0: 0, d1: 1, d2: 2 });
+nalgebra_vertex_attribute!(u32_u32_u32_u32_float, u32, Vector4, Point4 { d0: 0, d1: 1, d2: 2, d3: 3 });
+
+nalgebra_unit_vertex_attribute!(f32_f32, f32, Vector2);
+nalgebra_unit_vertex_attribute!(f32_f32_f32, f32, Vector3);
+nalgebra_unit_vertex_attribute!(f32_f32_f32_f32, f32, Vector4);
+nalgebra_unit_vertex_attribute!(f64_f64, f64, Vector2);
+nalgebra_unit_vertex_attribute!(f64_f64_f64, f64, Vector3);
+nalgebra_unit_vertex_attribute!(f64_f64_f64_f64, f64, Vector4);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locations<V: VertexAttribPointers>() -> Vec<(&'static str, usize)> {
+        V::vertex_attribs()
+            .iter()
+            .map(|attrib| (attrib.name, attrib.location))
+            .collect()
+    }
+
+    #[derive(VertexAttribPointers, Copy, Clone)]
+    #[repr(C, packed)]
+    struct Implicit {
+        pos: f32_f32_f32,
+        clr: u8_u8_u8_u8_float,
+        uv: f32_f32,
+    }
+
+    #[test]
+    fn implicit_locations_follow_field_order() {
+        assert_eq!(locations::<Implicit>(), vec![("pos", 0), ("clr", 1), ("uv", 2)]);
+    }
+
+    #[derive(VertexAttribPointers, Copy, Clone)]
+    #[repr(C, packed)]
+    struct Explicit {
+        #[location = 3]
+        pos: f32_f32_f32,
+        #[location = 0]
+        clr: f32_f32_f32_f32,
+    }
+
+    #[test]
+    fn explicit_locations_are_kept() {
+        assert_eq!(locations::<Explicit>(), vec![("pos", 3), ("clr", 0)]);
+    }
+
+    #[derive(VertexAttribPointers, Copy, Clone)]
+    #[repr(C, packed)]
+    struct Doubles(f64_f64_f64, f64_f64, f64_f64_f64_f64, f32_);
+
+    #[test]
+    fn dvec3_and_dvec4_take_two_locations() {
+        assert_eq!(f64_f64::LOCATIONS, 1);
+        assert_eq!(f64_f64_f64::LOCATIONS, 2);
+        assert_eq!(<na::Vector4<f64> as VertexAttribute>::LOCATIONS, 2);
+        assert_eq!(locations::<Doubles>(), vec![("0", 0), ("1", 2), ("2", 3), ("3", 5)]);
+    }
+
+    #[derive(VertexAttribPointers, Copy, Clone)]
+    #[repr(C, packed)]
+    struct ExplicitDoubles {
+        #[location = 0]
+        pos: f64_f64_f64,
+        #[location = 2]
+        clr: f32_f32_f32,
+    }
+
+    #[test]
+    fn explicit_locations_next_to_dvec3() {
+        assert_eq!(locations::<ExplicitDoubles>(), vec![("pos", 0), ("clr", 2)]);
+    }
+
+    #[test]
+    fn overlapping_location_ranges() {
+        assert!(locations_overlap(0, 2, 1, 1));
+        assert!(locations_overlap(1, 1, 0, 2));
+        assert!(!locations_overlap(0, 2, 2, 1));
+        assert!(!locations_overlap(3, 1, 0, 3));
     }
 }
@@ -0,0 +1,313 @@
+use crate::render_gl::preprocessor::PreprocessedSource;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// One message of a shader compile or program link info log.
+/// Displays like a rustc diagnostic, with the offending line when known.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Resource the message points into, when the log names a line
+    /// of a source loaded from resources
+    pub file: Option<String>,
+    /// 1-based line in `file`, or in the compiled source without one
+    pub line: Option<usize>,
+    /// 1-based column, only Mesa reports it
+    pub column: Option<usize>,
+    pub message: String,
+    /// Text of `line`
+    pub source_line: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+
+        let line = match self.line {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+
+        // Width of the line number gutter
+        let gutter = " ".repeat(line.to_string().len());
+
+        write!(f, "\n{}--> {}", gutter, self.file.as_deref().unwrap_or("<source>"))?;
+        write!(f, ":{}", line)?;
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+
+        if let Some(source_line) = &self.source_line {
+            write!(f, "\n{} |", gutter)?;
+            write!(f, "\n{} | {}", line, source_line)?;
+            write!(f, "\n{} |", gutter)?;
+            if let Some(column) = self.column {
+                write!(f, " {}^", " ".repeat(column.saturating_sub(1)))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a driver info log into one diagnostic per message. Handles
+///
+/// * Mesa: `0:12(5): error: ...`
+/// * NVIDIA: `0(12) : error C0000: ...`
+/// * AMD / Intel: `ERROR: 0:12: ...`
+///
+/// Lines in none of these formats become diagnostics without a location,
+/// with `default_severity` unless they start with `error:`, `warning:`, ...
+/// With the `source` the log was produced for, line numbers are mapped back
+/// to the resource and line they came from.
+pub fn parse_info_log(
+    log: &str,
+    source: Option<&PreprocessedSource>,
+    default_severity: Severity,
+) -> Vec<Diagnostic> {
+    let source_text = source.map(|source| source.source.to_string_lossy());
+    let source_lines = source_text
+        .as_ref()
+        .map(|text| text.lines().collect::<Vec<&str>>())
+        .unwrap_or_default();
+
+    log.lines()
+        .map(str::trim_end)
+        .filter(|log_line| !log_line.trim().is_empty())
+        .map(|log_line| {
+            let mut diagnostic = parse_log_line(log_line.trim_start(), default_severity);
+
+            if let Some(line) = diagnostic.line {
+                diagnostic.source_line = line
+                    .checked_sub(1)
+                    .and_then(|index| source_lines.get(index))
+                    .map(|source_line| source_line.to_string());
+
+                if let Some(origin) = source.and_then(|source| source.line_map.origin(line)) {
+                    diagnostic.file = Some(origin.file.clone());
+                    diagnostic.line = Some(origin.line);
+                }
+            }
+
+            diagnostic
+        })
+        .collect()
+}
+
+/// Rendered `diagnostics`, one after another
+pub fn render(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn parse_log_line(log_line: &str, default_severity: Severity) -> Diagnostic {
+    let (start, end, line) = match find_log_line_reference(log_line) {
+        Some(reference) => reference,
+        None => {
+            let (severity, message) =
+                split_severity(log_line).unwrap_or((default_severity, log_line));
+            return Diagnostic {
+                severity,
+                file: None,
+                line: None,
+                column: None,
+                message: message.into(),
+                source_line: None,
+            };
+        }
+    };
+
+    // `ERROR: ` / `WARNING: ` before the reference
+    let prefix_severity = severity_from_word(log_line[..start].trim().trim_end_matches(':'));
+
+    // Mesa follows the line with `(column)`
+    let mut rest = &log_line[end..];
+    let mut column = None;
+    if let Some(after_paren) = rest.strip_prefix('(') {
+        if let Some(close) = after_paren.find(')') {
+            column = after_paren[..close].parse().ok();
+            rest = &after_paren[close + 1..];
+        }
+    }
+    let rest = rest.trim_start().trim_start_matches(':').trim_start();
+
+    let (severity, message) = match prefix_severity {
+        Some(severity) => (severity, rest),
+        None => split_severity(rest).unwrap_or((default_severity, rest)),
+    };
+
+    Diagnostic {
+        severity,
+        file: None,
+        line: Some(line),
+        column,
+        message: message.into(),
+        source_line: None,
+    }
+}
+
+/// Finds the source line reference in one line of a compiler info log.
+/// Returns the byte range of the reference and the referenced line.
+///
+/// * Mesa: `0:12(5): error: ...`
+/// * NVIDIA: `0(12) : error C0000: ...`
+/// * AMD / Intel: `ERROR: 0:12: ...`
+fn find_log_line_reference(log_line: &str) -> Option<(usize, usize, usize)> {
+    let trimmed = log_line.trim_start();
+    let mut start = log_line.len() - trimmed.len();
+
+    // Skip the `ERROR: ` / `WARNING: ` prefix
+    for prefix in ["ERROR: ", "WARNING: "].iter() {
+        if trimmed.starts_with(prefix) {
+            start += prefix.len();
+        }
+    }
+
+    let rest = &log_line[start..];
+    let source_len = rest.chars().take_while(char::is_ascii_digit).count();
+    if source_len == 0 {
+        return None;
+    }
+
+    let after_source = &rest[source_len..];
+    let (line_start, closing) = if after_source.starts_with(':') {
+        (source_len + 1, None)
+    } else if after_source.starts_with('(') {
+        (source_len + 1, Some(')'))
+    } else {
+        return None;
+    };
+
+    let line_len = rest[line_start..]
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .count();
+    if line_len == 0 {
+        return None;
+    }
+
+    let line = rest[line_start..line_start + line_len].parse().ok()?;
+    let mut end = line_start + line_len;
+
+    match closing {
+        Some(closing) if rest[end..].starts_with(closing) => end += 1,
+        Some(_) => return None,
+        None => {}
+    }
+
+    Some((start, start + end, line))
+}
+
+/// `error C1008: message` -> (Error, `message`)
+fn split_severity(text: &str) -> Option<(Severity, &str)> {
+    let colon = text.find(':')?;
+    let word = text[..colon].split_whitespace().next()?;
+    let severity = severity_from_word(word)?;
+    Some((severity, text[colon + 1..].trim_start()))
+}
+
+fn severity_from_word(word: &str) -> Option<Severity> {
+    match word.to_ascii_lowercase().as_str() {
+        "error" | "fatal" => Some(Severity::Error),
+        "warning" => Some(Severity::Warning),
+        "note" | "info" => Some(Severity::Note),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_gl::preprocessor;
+
+    fn parse_one(log: &str) -> Diagnostic {
+        let mut diagnostics = parse_info_log(log, None, Severity::Error);
+        assert_eq!(diagnostics.len(), 1);
+        diagnostics.remove(0)
+    }
+
+    #[test]
+    fn parses_mesa_logs() {
+        let diagnostic = parse_one("0:12(5): error: `foo' undeclared");
+
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.column, Some(5));
+        assert_eq!(diagnostic.message, "`foo' undeclared");
+    }
+
+    #[test]
+    fn parses_nvidia_logs() {
+        let diagnostic = parse_one("0(12) : warning C7022: unrecognized profile specifier");
+
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.column, None);
+        assert_eq!(diagnostic.message, "unrecognized profile specifier");
+    }
+
+    #[test]
+    fn parses_amd_logs() {
+        let diagnostic = parse_one("ERROR: 0:12: 'foo' : undeclared identifier");
+
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.column, None);
+        assert_eq!(diagnostic.message, "'foo' : undeclared identifier");
+    }
+
+    #[test]
+    fn keeps_unknown_lines_without_location() {
+        let diagnostics = parse_info_log("Vertex info\n\nlink failed", None, Severity::Note);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].severity, Severity::Note);
+        assert_eq!(diagnostics[1].line, None);
+        assert_eq!(diagnostics[1].message, "link failed");
+    }
+
+    #[test]
+    fn maps_lines_back_through_the_line_map() {
+        let load = |name: &str| {
+            Ok(match name {
+                "main.frag" => "#version 330\n#include \"a.glsl\"\nvoid main() {}",
+                _ => "float a;\nfoo;",
+            }
+            .to_string())
+        };
+        let source = preprocessor::preprocess_with(&load, "main.frag", &[("A", "1")]).unwrap();
+
+        // `#version`, the define, then the two lines of `a.glsl`
+        let diagnostic =
+            parse_info_log("0:4(1): error: syntax error", Some(&source), Severity::Error).remove(0);
+
+        assert_eq!(diagnostic.file.as_deref(), Some("a.glsl"));
+        assert_eq!(diagnostic.line, Some(2));
+        assert_eq!(diagnostic.source_line.as_deref(), Some("foo;"));
+    }
+}
@@ -0,0 +1,44 @@
+//! Parsing of single preprocessor directive lines. The `validate_shaders`
+//! build script includes this file by path to read shaders exactly like
+//! `preprocessor` does, so it may only use `std`.
+
+/// Whether `line` is `#directive ...`, allowing whitespace around the `#`
+pub(crate) fn is_directive(line: &str, directive: &str) -> bool {
+    line.trim_start()
+        .strip_prefix('#')
+        .map(|rest| rest.trim_start())
+        .and_then(|rest| rest.strip_prefix(directive))
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+/// `vertex` from `#pragma stage vertex`
+pub(crate) fn parse_stage_pragma(line: &str) -> Option<&str> {
+    if !is_directive(line, "pragma") {
+        return None;
+    }
+
+    let mut words = line.trim().trim_start_matches('#').split_whitespace().skip(1);
+    match (words.next(), words.next(), words.next()) {
+        (Some("stage"), Some(stage), None) => Some(stage),
+        _ => None,
+    }
+}
+
+/// `common/lighting.glsl` from `#include "common/lighting.glsl"` (or `<...>`)
+pub(crate) fn parse_include_path(line: &str) -> Option<&str> {
+    let argument = line.trim().trim_start_matches('#').trim_start();
+    let argument = argument.strip_prefix("include")?.trim();
+
+    let (open, close) = match argument.chars().next()? {
+        '"' => ('"', '"'),
+        '<' => ('<', '>'),
+        _ => return None,
+    };
+
+    let path = argument.strip_prefix(open)?.strip_suffix(close)?;
+    if path.is_empty() {
+        return None;
+    }
+
+    Some(path)
+}
@@ -0,0 +1,115 @@
+use crate::render_gl::buffer::{self, IndexType};
+use crate::render_gl::data::VertexAttribPointers;
+use gl;
+
+/// Vertex and index data uploaded once, drawn with `DrawElements`
+pub struct IndexedMesh<V, I>
+where
+    V: VertexAttribPointers + 'static,
+    I: IndexType,
+{
+    gl: gl::Gl,
+    vao: buffer::VertexArray,
+    _vbo: buffer::VertexBuffer<V>,
+    ibo: buffer::IndexBuffer<I>,
+    primitive_restart: bool,
+}
+
+impl<V, I> IndexedMesh<V, I>
+where
+    V: VertexAttribPointers + 'static,
+    I: IndexType,
+{
+    pub fn new(gl: &gl::Gl, vertices: &[V], indices: &[I]) -> IndexedMesh<V, I> {
+        let vao = buffer::VertexArray::new(gl);
+        let mut vbo = buffer::VertexBuffer::new(gl);
+        let mut ibo = buffer::IndexBuffer::new(gl);
+
+        vbo.bind();
+        vbo.static_draw_data(vertices);
+        vbo.unbind();
+
+        ibo.bind();
+        ibo.static_draw_data(indices);
+        ibo.unbind();
+
+        vao.set_vertex_buffer(&vbo);
+        vao.set_index_buffer(&ibo);
+
+        IndexedMesh {
+            gl: gl.clone(),
+            vao,
+            _vbo: vbo,
+            ibo,
+            primitive_restart: false,
+        }
+    }
+
+    /// Treats `I::RESTART_INDEX` in the index data as the end of a primitive,
+    /// e.g. to draw many triangle strips in one call
+    pub fn with_primitive_restart(mut self, enabled: bool) -> Self {
+        self.primitive_restart = enabled;
+        self
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.ibo.len()
+    }
+
+    /// Draws all indices
+    pub fn render(&self, mode: gl::types::GLenum) {
+        self.render_range_base_vertex(mode, 0, self.ibo.len(), 0);
+    }
+
+    /// Draws all indices, adding `base_vertex` to each index before
+    /// fetching the vertex
+    pub fn render_base_vertex(&self, mode: gl::types::GLenum, base_vertex: i32) {
+        self.render_range_base_vertex(mode, 0, self.ibo.len(), base_vertex);
+    }
+
+    /// Draws `count` indices starting from index `first`, adding `base_vertex`
+    /// to each index before fetching the vertex
+    pub fn render_range_base_vertex(
+        &self,
+        mode: gl::types::GLenum,
+        first: usize,
+        count: usize,
+        base_vertex: i32,
+    ) {
+        debug_assert!(
+            first + count <= self.ibo.len(),
+            "Index range {}..{} out of bounds for mesh with {} indices",
+            first,
+            first + count,
+            self.ibo.len()
+        );
+
+        self.vao.bind();
+        unsafe {
+            // `PRIMITIVE_RESTART_FIXED_INDEX` would pick the index by itself,
+            // but only from GL 4.3 on
+            if self.primitive_restart {
+                self.gl.Enable(gl::PRIMITIVE_RESTART);
+                self.gl.PrimitiveRestartIndex(restart_index::<I>());
+            }
+
+            self.gl.DrawElementsBaseVertex(
+                mode,                                                                // mode
+                count as gl::types::GLsizei,                                         // Number of indices
+                I::GL_TYPE,                                                          // Type of the indices
+                (first * ::std::mem::size_of::<I>()) as *const gl::types::GLvoid,    // Offset of the first index
+                base_vertex,                                                         // Added to each index
+            );
+
+            if self.primitive_restart {
+                self.gl.Disable(gl::PRIMITIVE_RESTART);
+            }
+        }
+    }
+}
+
+/// `I::RESTART_INDEX` as the `GLuint` `PrimitiveRestartIndex` takes,
+/// the largest value that fits in `I`
+fn restart_index<I: IndexType>() -> gl::types::GLuint {
+    gl::types::GLuint::MAX >> (32 - 8 * ::std::mem::size_of::<I>())
+}
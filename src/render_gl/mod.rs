@@ -1,9 +1,31 @@
+pub mod block;
 pub mod buffer;
+pub mod capabilities;
+pub mod compute;
 pub mod data;
+pub mod diagnostics;
+pub mod preprocessor;
+pub mod reflection;
+pub mod spirv;
 mod shader;
 mod viewport;
 mod color_buffer;
+mod directives;
+mod mesh;
+mod program_cache;
+mod reloadable;
+mod ring_buffer;
+mod uniform;
+mod variants;
 
 pub use self::shader::{Error, Program, Shader};
 pub use self::viewport::Viewport;
-pub use self::color_buffer::ColorBuffer;
\ No newline at end of file
+pub use self::color_buffer::ColorBuffer;
+pub use self::compute::ComputeProgram;
+pub use self::mesh::IndexedMesh;
+pub use self::program_cache::ProgramCache;
+pub use self::reloadable::ReloadableProgram;
+pub use self::ring_buffer::RingBuffer;
+pub use self::spirv::Specialization;
+pub use self::uniform::Uniform;
+pub use self::variants::{Features, ProgramVariants};
\ No newline at end of file
@@ -0,0 +1,469 @@
+use crate::render_gl::directives::{is_directive, parse_include_path, parse_stage_pragma};
+use crate::render_gl::shader::Error;
+use crate::resources::Resources;
+use std::ffi::CString;
+
+/// File name the line map gives injected `#define`s
+const DEFINES_FILE: &str = "<defines>";
+
+/// Shader source with its includes resolved and defines injected
+pub struct PreprocessedSource {
+    pub source: CString,
+    pub line_map: LineMap,
+}
+
+/// Where a line of preprocessed source originally came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineOrigin {
+    /// Resource name, or `<defines>` for injected `#define`s
+    pub file: String,
+    /// 1-based line in `file`
+    pub line: usize,
+}
+
+/// Maps 1-based preprocessed line numbers back to their origin
+#[derive(Clone, Debug, Default)]
+pub struct LineMap {
+    lines: Vec<LineOrigin>,
+}
+
+impl LineMap {
+    pub fn origin(&self, line: usize) -> Option<&LineOrigin> {
+        line.checked_sub(1).and_then(|index| self.lines.get(index))
+    }
+
+    /// Resources the source was assembled from, the root file first
+    pub fn files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = Vec::new();
+        for origin in self.lines.iter() {
+            if origin.file != DEFINES_FILE && !files.contains(&origin.file.as_str()) {
+                files.push(&origin.file);
+            }
+        }
+        files
+    }
+}
+
+/// Loads shader resource `name`, recursively replacing `#include "other"`
+/// lines with the contents of resource `other` (relative to the resources
+/// root), and adds `#define NAME VALUE` for each of `defines` right after
+/// the `#version` line.
+pub fn preprocess(
+    res: &Resources,
+    name: &str,
+    defines: &[(&str, &str)],
+) -> Result<PreprocessedSource, Error> {
+    let load = |name: &str| {
+        res.load_cstring(name)
+            .map(|source| source.to_string_lossy().into_owned())
+            .map_err(|e| Error::ResourceLoad {
+                name: name.into(),
+                inner: e,
+            })
+    };
+
+    preprocess_with(&load, name, defines)
+}
+
+/// Like `preprocess`, loading sources through `load` instead of from resources
+pub(crate) fn preprocess_with(
+    load: &dyn Fn(&str) -> Result<String, Error>,
+    name: &str,
+    defines: &[(&str, &str)],
+) -> Result<PreprocessedSource, Error> {
+    let mut output = Output {
+        source: String::new(),
+        line_map: LineMap::default(),
+    };
+    let mut include_stack = Vec::new();
+
+    // A nul would end the source early, a line break would shift the line map
+    if let Some(&(define, _)) = defines.iter().find(|(define, value)| {
+        define.contains(['\0', '\n', '\r']) || value.contains(['\0', '\n', '\r'])
+    }) {
+        return Err(Error::InvalidDefine {
+            name: name.into(),
+            define: define.into(),
+        });
+    }
+
+    expand(load, name, defines, &mut include_stack, &mut output)?;
+
+    Ok(PreprocessedSource {
+        // Resources refuse files containing nul and the defines were checked
+        // above, so the joined source can't have any
+        source: CString::new(output.source).expect("Preprocessed source contains nul"),
+        line_map: output.line_map,
+    })
+}
+
+/// One `#pragma stage NAME` section of a multi-stage source
+pub struct StageSource {
+    /// `vertex`, `fragment`, ...
+    pub stage: String,
+    /// Where the section's `#pragma stage` is
+    pub pragma: LineOrigin,
+    /// The shared code before the first section followed by the section
+    pub source: PreprocessedSource,
+}
+
+/// Splits a preprocessed multi-stage source at its `#pragma stage NAME`
+/// lines. Everything before the first one (`#version`, defines, common
+/// functions) is shared by all stages. The line maps of the stage sources
+/// still point into the combined file.
+pub fn split_stages(source: &PreprocessedSource) -> Vec<StageSource> {
+    let text = source.source.to_string_lossy();
+    let mut shared = Output {
+        source: String::new(),
+        line_map: LineMap::default(),
+    };
+    let mut stages: Vec<(String, LineOrigin, Output)> = Vec::new();
+
+    for (line, origin) in text.lines().zip(source.line_map.lines.iter()) {
+        if let Some(stage) = parse_stage_pragma(line) {
+            stages.push((
+                stage.to_owned(),
+                origin.clone(),
+                Output {
+                    source: shared.source.clone(),
+                    line_map: shared.line_map.clone(),
+                },
+            ));
+            continue;
+        }
+
+        let output = match stages.last_mut() {
+            Some((_, _, output)) => output,
+            None => &mut shared,
+        };
+        output.push_line(line, &origin.file, origin.line);
+    }
+
+    stages
+        .into_iter()
+        .map(|(stage, pragma, output)| StageSource {
+            stage,
+            pragma,
+            source: PreprocessedSource {
+                source: CString::new(output.source).expect("Preprocessed source contains nul"),
+                line_map: output.line_map,
+            },
+        })
+        .collect()
+}
+
+struct Output {
+    source: String,
+    line_map: LineMap,
+}
+
+impl Output {
+    fn push_line(&mut self, line: &str, file: &str, line_number: usize) {
+        self.source.push_str(line);
+        self.source.push('\n');
+        self.line_map.lines.push(LineOrigin {
+            file: file.to_owned(),
+            line: line_number,
+        });
+    }
+}
+
+fn expand(
+    load: &dyn Fn(&str) -> Result<String, Error>,
+    name: &str,
+    defines: &[(&str, &str)],
+    include_stack: &mut Vec<String>,
+    output: &mut Output,
+) -> Result<(), Error> {
+    if include_stack.iter().any(|included| included == name) {
+        let mut chain = include_stack.clone();
+        chain.push(name.to_owned());
+        return Err(Error::IncludeCycle {
+            name: include_stack[0].clone(),
+            chain: chain.join(" -> "),
+        });
+    }
+
+    let source = load(name)?;
+
+    include_stack.push(name.to_owned());
+
+    // Defines go after the `#version` of the top level file,
+    // or at the very top when it has none
+    let is_root = include_stack.len() == 1;
+    let has_version = source.lines().any(|line| is_directive(line, "version"));
+    if is_root && !has_version {
+        push_defines(defines, output);
+    }
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+
+        if is_directive(line, "include") {
+            let included = parse_include_path(line).ok_or_else(|| Error::MalformedInclude {
+                name: name.into(),
+                line: line_number,
+            })?;
+            expand(load, included, defines, include_stack, output)?;
+        } else {
+            output.push_line(line, name, line_number);
+
+            if is_root && is_directive(line, "version") {
+                push_defines(defines, output);
+            }
+        }
+    }
+
+    include_stack.pop();
+    Ok(())
+}
+
+fn push_defines(defines: &[(&str, &str)], output: &mut Output) {
+    for (index, (define, value)) in defines.iter().enumerate() {
+        output.push_line(&format!("#define {} {}", define, value), DEFINES_FILE, index + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io;
+
+    /// Preprocesses `name` from the in-memory `files`
+    fn preprocess_files(
+        files: &[(&str, &str)],
+        name: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<PreprocessedSource, Error> {
+        let files = files.iter().cloned().collect::<HashMap<&str, &str>>();
+        let load = |name: &str| match files.get(name) {
+            Some(source) => Ok(source.to_string()),
+            None => Err(Error::ResourceLoad {
+                name: name.into(),
+                inner: io::Error::from(io::ErrorKind::NotFound).into(),
+            }),
+        };
+
+        preprocess_with(&load, name, defines)
+    }
+
+    fn lines(source: &PreprocessedSource) -> Vec<String> {
+        source.source.to_str().unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn expands_nested_includes() {
+        let source = preprocess_files(
+            &[
+                ("main.frag", "#version 330\n#include \"a.glsl\"\nvoid main() {}"),
+                ("a.glsl", "float a;\n#include <b.glsl>"),
+                ("b.glsl", "float b;"),
+            ],
+            "main.frag",
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(lines(&source), vec!["#version 330", "float a;", "float b;", "void main() {}"]);
+        assert_eq!(source.line_map.files(), vec!["main.frag", "a.glsl", "b.glsl"]);
+    }
+
+    #[test]
+    fn detects_include_cycles() {
+        let result = preprocess_files(
+            &[
+                ("main.frag", "#include \"a.glsl\""),
+                ("a.glsl", "#include \"b.glsl\""),
+                ("b.glsl", "#include \"a.glsl\""),
+            ],
+            "main.frag",
+            &[],
+        );
+
+        match result {
+            Err(Error::IncludeCycle { name, chain }) => {
+                assert_eq!(name, "main.frag");
+                assert_eq!(chain, "main.frag -> a.glsl -> b.glsl -> a.glsl");
+            }
+            _ => panic!("expected an include cycle"),
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_includes() {
+        let result = preprocess_files(&[("main.frag", "\n#include a.glsl")], "main.frag", &[]);
+
+        match result {
+            Err(Error::MalformedInclude { name, line }) => {
+                assert_eq!(name, "main.frag");
+                assert_eq!(line, 2);
+            }
+            _ => panic!("expected a malformed include"),
+        }
+    }
+
+    #[test]
+    fn puts_defines_after_version() {
+        let source = preprocess_files(
+            &[("main.frag", "// comment\n#version 330\nvoid main() {}")],
+            "main.frag",
+            &[("A", "1"), ("B", "2")],
+        )
+        .unwrap();
+
+        assert_eq!(
+            lines(&source),
+            vec!["// comment", "#version 330", "#define A 1", "#define B 2", "void main() {}"]
+        );
+    }
+
+    #[test]
+    fn rejects_defines_with_nul() {
+        let result = preprocess_files(
+            &[("main.frag", "void main() {}")],
+            "main.frag",
+            &[("A", "1"), ("B", "2\0")],
+        );
+
+        match result {
+            Err(Error::InvalidDefine { name, define }) => {
+                assert_eq!(name, "main.frag");
+                assert_eq!(define, "B");
+            }
+            _ => panic!("expected an invalid define"),
+        }
+    }
+
+    #[test]
+    fn puts_defines_first_without_version() {
+        let source =
+            preprocess_files(&[("main.frag", "void main() {}")], "main.frag", &[("A", "1")])
+                .unwrap();
+
+        assert_eq!(lines(&source), vec!["#define A 1", "void main() {}"]);
+    }
+
+    #[test]
+    fn maps_lines_back_to_their_origin() {
+        let source = preprocess_files(
+            &[
+                ("main.frag", "#version 330\n#include \"a.glsl\"\nvoid main() {}"),
+                ("a.glsl", "float a;\nfloat b;"),
+            ],
+            "main.frag",
+            &[("A", "1")],
+        )
+        .unwrap();
+
+        let origin = |line| {
+            let origin = source.line_map.origin(line).unwrap();
+            (origin.file.as_str(), origin.line)
+        };
+        assert_eq!(origin(1), ("main.frag", 1));
+        assert_eq!(origin(2), (DEFINES_FILE, 1));
+        assert_eq!(origin(3), ("a.glsl", 1));
+        assert_eq!(origin(4), ("a.glsl", 2));
+        assert_eq!(origin(5), ("main.frag", 3));
+        assert!(source.line_map.origin(0).is_none());
+        assert!(source.line_map.origin(6).is_none());
+    }
+
+    #[test]
+    fn splits_stages_after_a_shared_preamble() {
+        let source = preprocess_files(
+            &[
+                (
+                    "main.glsl",
+                    "#version 330\n#include \"common.glsl\"\n#pragma stage vertex\n\
+                     void main() {}\n#pragma stage fragment\nvoid main() { f(); }",
+                ),
+                ("common.glsl", "float f();"),
+            ],
+            "main.glsl",
+            &[],
+        )
+        .unwrap();
+
+        let stages = split_stages(&source);
+
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].stage, "vertex");
+        assert_eq!(lines(&stages[0].source), vec!["#version 330", "float f();", "void main() {}"]);
+        assert_eq!(stages[1].stage, "fragment");
+        assert_eq!(
+            lines(&stages[1].source),
+            vec!["#version 330", "float f();", "void main() { f(); }"]
+        );
+    }
+
+    #[test]
+    fn maps_stage_lines_back_to_the_combined_file() {
+        let source = preprocess_files(
+            &[
+                (
+                    "main.glsl",
+                    "#version 330\n#include \"common.glsl\"\n#pragma stage vertex\n\
+                     void main() {}\n#pragma stage fragment\nvoid main() { f(); }",
+                ),
+                ("common.glsl", "float f();"),
+            ],
+            "main.glsl",
+            &[("A", "1")],
+        )
+        .unwrap();
+
+        let stages = split_stages(&source);
+        fn origin(stage: &StageSource, line: usize) -> (&str, usize) {
+            let origin = stage.source.line_map.origin(line).unwrap();
+            (origin.file.as_str(), origin.line)
+        }
+
+        assert_eq!(stages[0].pragma, LineOrigin { file: "main.glsl".into(), line: 3 });
+        assert_eq!(stages[1].pragma, LineOrigin { file: "main.glsl".into(), line: 5 });
+        for stage in &stages {
+            assert_eq!(origin(stage, 1), ("main.glsl", 1));
+            assert_eq!(origin(stage, 2), (DEFINES_FILE, 1));
+            assert_eq!(origin(stage, 3), ("common.glsl", 1));
+        }
+        assert_eq!(origin(&stages[0], 4), ("main.glsl", 4));
+        assert_eq!(origin(&stages[1], 4), ("main.glsl", 6));
+        assert!(stages[0].source.line_map.origin(5).is_none());
+    }
+
+    #[test]
+    fn only_splits_at_stage_pragmas() {
+        let source = preprocess_files(
+            &[(
+                "main.glsl",
+                "#pragma optimize(off)\n#  pragma   stage   vertex\nvoid main() {}\n\
+                 #pragma stage fragment extra\n#pragma stages fragment",
+            )],
+            "main.glsl",
+            &[],
+        )
+        .unwrap();
+
+        let stages = split_stages(&source);
+
+        assert_eq!(stages.len(), 1);
+        assert_eq!(stages[0].stage, "vertex");
+        assert_eq!(
+            lines(&stages[0].source),
+            vec![
+                "#pragma optimize(off)",
+                "void main() {}",
+                "#pragma stage fragment extra",
+                "#pragma stages fragment",
+            ]
+        );
+    }
+
+    #[test]
+    fn has_no_stages_without_stage_pragmas() {
+        let source =
+            preprocess_files(&[("main.glsl", "void main() {}")], "main.glsl", &[]).unwrap();
+
+        assert!(split_stages(&source).is_empty());
+    }
+}
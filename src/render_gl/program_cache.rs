@@ -0,0 +1,150 @@
+use crate::render_gl::capabilities;
+use crate::render_gl::preprocessor::PreprocessedSource;
+use crate::render_gl::shader::{self, Error, Program, Shader};
+use crate::resources::Resources;
+use gl;
+
+/// FNV-1a offset basis and prime, the hash has to stay the same across
+/// runs and Rust versions for cache files to be found again
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Linked program binaries saved under a resources directory, so later
+/// runs on the same driver can skip compiling and linking
+pub struct ProgramCache {
+    dir: String,
+    // Vendor, renderer and version, binaries only load on the driver that made them
+    driver: String,
+}
+
+impl ProgramCache {
+    /// Cache in resources directory `dir`, e.g. `cache/programs`
+    pub fn new(gl: &gl::Gl, dir: &str) -> ProgramCache {
+        ProgramCache {
+            dir: dir.trim_end_matches('/').into(),
+            driver: format!(
+                "{}\n{}\n{}",
+                capabilities::string(gl, gl::VENDOR),
+                capabilities::string(gl, gl::RENDERER),
+                capabilities::string(gl, gl::VERSION)
+            ),
+        }
+    }
+
+    /// Loads program `name` from the cache when the driver accepts the
+    /// cached binary, otherwise builds it from source and caches it.
+    /// Cache write failures only cost the next run a source build.
+    pub fn load(
+        &self,
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<Program, Error> {
+        let stages = shader::preprocessed_stages(res, name, defines)?;
+        let source_hash = source_hash(&stages);
+
+        let cache_name = format!("{}/{:016x}.bin", self.dir, self.key(source_hash));
+
+        if let Some(program) = res
+            .load_bytes(&cache_name)
+            .ok()
+            .and_then(|bytes| self.program_from_cache_file(gl, &bytes, source_hash, &stages))
+        {
+            return Ok(program);
+        }
+
+        let shaders = stages
+            .iter()
+            .map(|(resource_name, kind, source)| {
+                Shader::from_preprocessed(gl, resource_name, *kind, source)
+            })
+            .collect::<Result<Vec<Shader>, Error>>()?;
+
+        let program = Program::link(gl, &shaders, true).map_err(|log| Error::link(name, &log))?;
+
+        if let Some((format, binary)) = program.binary() {
+            let mut bytes = self.cache_file_header(source_hash);
+            bytes.extend_from_slice(&format.to_le_bytes());
+            bytes.extend_from_slice(&binary);
+            let _ = res.save_bytes(&cache_name, &bytes);
+        }
+
+        Ok(program)
+    }
+
+    /// Cache file name, hash of the driver and the stage sources
+    fn key(&self, source_hash: u64) -> u64 {
+        let hash = fnv1a(FNV_OFFSET_BASIS, self.driver.as_bytes());
+        fnv1a(hash, &source_hash.to_le_bytes())
+    }
+
+    /// Driver string and source hash the binary was made for, checked before
+    /// handing the binary to the driver since file names can collide
+    fn cache_file_header(&self, source_hash: u64) -> Vec<u8> {
+        let mut header = (self.driver.len() as u32).to_le_bytes().to_vec();
+        header.extend_from_slice(self.driver.as_bytes());
+        header.extend_from_slice(&source_hash.to_le_bytes());
+        header
+    }
+
+    /// Cache files hold the header, the little endian binary format and the binary
+    fn program_from_cache_file(
+        &self,
+        gl: &gl::Gl,
+        bytes: &[u8],
+        source_hash: u64,
+        stages: &[(String, gl::types::GLenum, PreprocessedSource)],
+    ) -> Option<Program> {
+        let header = self.cache_file_header(source_hash);
+        let rest = bytes.strip_prefix(header.as_slice())?;
+
+        if rest.len() <= 4 {
+            return None;
+        }
+        let (format, binary) = rest.split_at(4);
+        let format = gl::types::GLenum::from_le_bytes([format[0], format[1], format[2], format[3]]);
+
+        let mut resources: Vec<String> = Vec::new();
+        for (_, _, source) in stages.iter() {
+            for file in source.line_map.files() {
+                if !resources.iter().any(|resource| resource == file) {
+                    resources.push(file.to_owned());
+                }
+            }
+        }
+
+        Program::from_binary(gl, format, binary, resources)
+    }
+}
+
+/// Hash of the kind and source of every preprocessed stage
+fn source_hash(stages: &[(String, gl::types::GLenum, PreprocessedSource)]) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+    for (_, kind, source) in stages.iter() {
+        let source = source.source.as_bytes();
+        hash = fnv1a(hash, &kind.to_le_bytes());
+        hash = fnv1a(hash, &(source.len() as u64).to_le_bytes());
+        hash = fnv1a(hash, source);
+    }
+    hash
+}
+
+/// Continues FNV-1a hash `hash` with `bytes`
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
@@ -0,0 +1,470 @@
+use crate::render_gl::data::{VertexAttribInfo, VertexAttribPointers};
+use gl;
+
+/// An active input, output or uniform of a linked program
+#[derive(Clone, Debug)]
+pub struct ProgramResource {
+    pub name: String,
+    /// GLSL type, e.g. `gl::FLOAT_VEC3`
+    pub gl_type: gl::types::GLenum,
+    /// Number of array elements, 1 for non-arrays
+    pub array_size: gl::types::GLint,
+    /// -1 for built-ins and members of uniform blocks
+    pub location: gl::types::GLint,
+}
+
+/// An active uniform or shader storage block of a linked program
+#[derive(Clone, Debug)]
+pub struct ProgramBlock {
+    pub name: String,
+    /// Binding point the block reads from
+    pub binding: gl::types::GLint,
+    /// Minimum buffer size in bytes needed to back the block
+    pub data_size: gl::types::GLint,
+    pub active_variables: gl::types::GLint,
+}
+
+/// Everything the linker kept active in a program. Queried with the
+/// program interface API, so it needs GL 4.3.
+#[derive(Clone, Debug)]
+pub struct ProgramReflection {
+    pub inputs: Vec<ProgramResource>,
+    pub outputs: Vec<ProgramResource>,
+    pub uniforms: Vec<ProgramResource>,
+    pub uniform_blocks: Vec<ProgramBlock>,
+    pub storage_blocks: Vec<ProgramBlock>,
+}
+
+impl ProgramReflection {
+    pub(crate) fn from_program(gl: &gl::Gl, program: gl::types::GLuint) -> ProgramReflection {
+        const VARIABLE_PROPS: [gl::types::GLenum; 3] = [gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION];
+        const BLOCK_PROPS: [gl::types::GLenum; 3] = [
+            gl::BUFFER_BINDING,
+            gl::BUFFER_DATA_SIZE,
+            gl::NUM_ACTIVE_VARIABLES,
+        ];
+
+        let variables = |interface| {
+            query_resources(gl, program, interface, &VARIABLE_PROPS)
+                .into_iter()
+                .map(|(name, values)| ProgramResource {
+                    name,
+                    gl_type: values[0] as gl::types::GLenum,
+                    array_size: values[1],
+                    location: values[2],
+                })
+                .collect()
+        };
+
+        let blocks = |interface| {
+            query_resources(gl, program, interface, &BLOCK_PROPS)
+                .into_iter()
+                .map(|(name, values)| ProgramBlock {
+                    name,
+                    binding: values[0],
+                    data_size: values[1],
+                    active_variables: values[2],
+                })
+                .collect()
+        };
+
+        ProgramReflection {
+            inputs: variables(gl::PROGRAM_INPUT),
+            outputs: variables(gl::PROGRAM_OUTPUT),
+            uniforms: variables(gl::UNIFORM),
+            uniform_blocks: blocks(gl::UNIFORM_BLOCK),
+            storage_blocks: blocks(gl::SHADER_STORAGE_BLOCK),
+        }
+    }
+
+    /// Input declared at `layout (location = N)`
+    pub fn input_at(&self, location: usize) -> Option<&ProgramResource> {
+        self.inputs
+            .iter()
+            .find(|input| input.location == location as gl::types::GLint)
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&ProgramResource> {
+        self.uniforms.iter().find(|uniform| uniform.name == name)
+    }
+
+    /// Compares the attributes of vertex `V` with the program inputs.
+    /// Returns one readable line per mismatch, empty when they agree.
+    pub fn vertex_mismatches<V: VertexAttribPointers>(&self) -> Vec<String> {
+        self.attrib_mismatches(&[V::vertex_attribs()])
+    }
+
+    /// Like `vertex_mismatches`, for attributes coming from several vertex
+    /// structs, e.g. a per-vertex and a per-instance one:
+    /// `attrib_mismatches(&[Vertex::vertex_attribs(), Instance::vertex_attribs()])`.
+    /// An attribute with fewer components than its input is fine, the
+    /// missing ones are filled in from (0, 0, 0, 1). Matrix inputs are fed
+    /// one column per location.
+    pub fn attrib_mismatches(&self, attrib_lists: &[Vec<VertexAttribInfo>]) -> Vec<String> {
+        let attribs = attrib_lists.iter().flatten().collect::<Vec<&VertexAttribInfo>>();
+        // Built-ins like gl_VertexID have no location
+        let slots = self
+            .inputs
+            .iter()
+            .filter(|input| input.location >= 0)
+            .flat_map(input_slots)
+            .collect::<Vec<InputSlot>>();
+        let mut mismatches = Vec::new();
+
+        for attrib in attribs.iter() {
+            match slots.iter().find(|slot| slot.location == attrib.location) {
+                None => mismatches.push(format!(
+                    "field `{}` feeds location {}, but the shader has no active input there",
+                    attrib.name, attrib.location
+                )),
+                Some(slot) if !attribute_fits(attrib.shader_type, slot.gl_type) => {
+                    mismatches.push(format!(
+                        "field `{}` at location {} is read as `{}`, but shader input `{}` is declared `{}`",
+                        attrib.name,
+                        attrib.location,
+                        glsl_type_name(attrib.shader_type),
+                        slot.name,
+                        glsl_type_name(slot.gl_type)
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+
+        for slot in slots.iter() {
+            let fed = attribs.iter().any(|attrib| {
+                (attrib.location..attrib.location + type_locations(attrib.shader_type))
+                    .contains(&slot.location)
+            });
+            if !fed {
+                mismatches.push(format!(
+                    "shader input `{}` at location {} is not fed by any field",
+                    slot.name, slot.location
+                ));
+            }
+        }
+
+        mismatches
+    }
+}
+
+/// One location of a vertex shader input, a single column for matrices
+/// and a single element for arrays
+#[derive(Debug, PartialEq)]
+struct InputSlot {
+    /// Input name, with the column and/or element index
+    name: String,
+    location: usize,
+    /// Scalar or vector type the location is read as
+    gl_type: gl::types::GLenum,
+}
+
+fn input_slots(input: &ProgramResource) -> Vec<InputSlot> {
+    let (column_type, columns) = matrix_columns(input.gl_type).unwrap_or((input.gl_type, 1));
+    let column_locations = type_locations(column_type);
+    let element_locations = columns * column_locations;
+
+    // Arrays are reported as `name[0]`
+    let name = input.name.trim_end_matches("[0]");
+    let is_array = input.array_size > 1 || name.len() != input.name.len();
+
+    let mut slots = Vec::new();
+    for element in 0..input.array_size.max(1) as usize {
+        let element_name = if is_array {
+            format!("{}[{}]", name, element)
+        } else {
+            name.to_owned()
+        };
+
+        for column in 0..columns {
+            slots.push(InputSlot {
+                name: if columns > 1 {
+                    format!("{}[{}]", element_name, column)
+                } else {
+                    element_name.clone()
+                },
+                location: input.location as usize
+                    + element * element_locations
+                    + column * column_locations,
+                gl_type: column_type,
+            });
+        }
+    }
+    slots
+}
+
+/// Column type and column count of matrix types, `None` for the rest
+fn matrix_columns(gl_type: gl::types::GLenum) -> Option<(gl::types::GLenum, usize)> {
+    let columns = match gl_type {
+        gl::FLOAT_MAT2 => (gl::FLOAT_VEC2, 2),
+        gl::FLOAT_MAT3 => (gl::FLOAT_VEC3, 3),
+        gl::FLOAT_MAT4 => (gl::FLOAT_VEC4, 4),
+        gl::FLOAT_MAT2x3 => (gl::FLOAT_VEC3, 2),
+        gl::FLOAT_MAT2x4 => (gl::FLOAT_VEC4, 2),
+        gl::FLOAT_MAT3x2 => (gl::FLOAT_VEC2, 3),
+        gl::FLOAT_MAT3x4 => (gl::FLOAT_VEC4, 3),
+        gl::FLOAT_MAT4x2 => (gl::FLOAT_VEC2, 4),
+        gl::FLOAT_MAT4x3 => (gl::FLOAT_VEC3, 4),
+        gl::DOUBLE_MAT2 => (gl::DOUBLE_VEC2, 2),
+        gl::DOUBLE_MAT3 => (gl::DOUBLE_VEC3, 3),
+        gl::DOUBLE_MAT4 => (gl::DOUBLE_VEC4, 4),
+        _ => return None,
+    };
+    Some(columns)
+}
+
+/// Number of locations a scalar or vector of `gl_type` takes up
+fn type_locations(gl_type: gl::types::GLenum) -> usize {
+    match gl_type {
+        gl::DOUBLE_VEC3 | gl::DOUBLE_VEC4 => 2,
+        _ => 1,
+    }
+}
+
+/// Whether an attribute read as `attrib_type` can feed an input declared
+/// `input_type`: same kind of components, and no more of them than the input has
+fn attribute_fits(attrib_type: gl::types::GLenum, input_type: gl::types::GLenum) -> bool {
+    match (vector_shape(attrib_type), vector_shape(input_type)) {
+        (Some((attrib_kind, attrib_components)), Some((input_kind, input_components))) => {
+            attrib_kind == input_kind && attrib_components <= input_components
+        }
+        _ => attrib_type == input_type,
+    }
+}
+
+/// Component type and count of scalar and vector types, `None` for the rest
+fn vector_shape(gl_type: gl::types::GLenum) -> Option<(gl::types::GLenum, usize)> {
+    let shape = match gl_type {
+        gl::FLOAT => (gl::FLOAT, 1),
+        gl::FLOAT_VEC2 => (gl::FLOAT, 2),
+        gl::FLOAT_VEC3 => (gl::FLOAT, 3),
+        gl::FLOAT_VEC4 => (gl::FLOAT, 4),
+        gl::DOUBLE => (gl::DOUBLE, 1),
+        gl::DOUBLE_VEC2 => (gl::DOUBLE, 2),
+        gl::DOUBLE_VEC3 => (gl::DOUBLE, 3),
+        gl::DOUBLE_VEC4 => (gl::DOUBLE, 4),
+        gl::INT => (gl::INT, 1),
+        gl::INT_VEC2 => (gl::INT, 2),
+        gl::INT_VEC3 => (gl::INT, 3),
+        gl::INT_VEC4 => (gl::INT, 4),
+        gl::UNSIGNED_INT => (gl::UNSIGNED_INT, 1),
+        gl::UNSIGNED_INT_VEC2 => (gl::UNSIGNED_INT, 2),
+        gl::UNSIGNED_INT_VEC3 => (gl::UNSIGNED_INT, 3),
+        gl::UNSIGNED_INT_VEC4 => (gl::UNSIGNED_INT, 4),
+        _ => return None,
+    };
+    Some(shape)
+}
+
+/// Name and `props` values of every active resource in `interface`
+fn query_resources(
+    gl: &gl::Gl,
+    program: gl::types::GLuint,
+    interface: gl::types::GLenum,
+    props: &[gl::types::GLenum],
+) -> Vec<(String, Vec<gl::types::GLint>)> {
+    let mut count: gl::types::GLint = 0;
+    let mut max_name_len: gl::types::GLint = 0;
+    unsafe {
+        gl.GetProgramInterfaceiv(program, interface, gl::ACTIVE_RESOURCES, &mut count);
+        gl.GetProgramInterfaceiv(program, interface, gl::MAX_NAME_LENGTH, &mut max_name_len);
+    }
+
+    (0..count as gl::types::GLuint)
+        .map(|index| {
+            let mut values: Vec<gl::types::GLint> = vec![0; props.len()];
+            let mut name: Vec<u8> = vec![0; max_name_len.max(1) as usize];
+            let mut name_len: gl::types::GLsizei = 0;
+
+            unsafe {
+                gl.GetProgramResourceiv(
+                    program,
+                    interface,
+                    index,
+                    props.len() as gl::types::GLsizei,
+                    props.as_ptr(),
+                    values.len() as gl::types::GLsizei,
+                    ::std::ptr::null_mut(),
+                    values.as_mut_ptr(),
+                );
+                gl.GetProgramResourceName(
+                    program,
+                    interface,
+                    index,
+                    name.len() as gl::types::GLsizei,
+                    &mut name_len,
+                    name.as_mut_ptr() as *mut gl::types::GLchar,
+                );
+            }
+
+            name.truncate(name_len as usize);
+            (String::from_utf8_lossy(&name).into_owned(), values)
+        })
+        .collect()
+}
+
+/// GLSL spelling of a type returned by reflection
+pub fn glsl_type_name(gl_type: gl::types::GLenum) -> &'static str {
+    match gl_type {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::DOUBLE => "double",
+        gl::DOUBLE_VEC2 => "dvec2",
+        gl::DOUBLE_VEC3 => "dvec3",
+        gl::DOUBLE_VEC4 => "dvec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::BOOL_VEC2 => "bvec2",
+        gl::BOOL_VEC3 => "bvec3",
+        gl::BOOL_VEC4 => "bvec4",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::FLOAT_MAT2x3 => "mat2x3",
+        gl::FLOAT_MAT2x4 => "mat2x4",
+        gl::FLOAT_MAT3x2 => "mat3x2",
+        gl::FLOAT_MAT3x4 => "mat3x4",
+        gl::FLOAT_MAT4x2 => "mat4x2",
+        gl::FLOAT_MAT4x3 => "mat4x3",
+        gl::DOUBLE_MAT2 => "dmat2",
+        gl::DOUBLE_MAT3 => "dmat3",
+        gl::DOUBLE_MAT4 => "dmat4",
+        gl::SAMPLER_1D => "sampler1D",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl::SAMPLER_BUFFER => "samplerBuffer",
+        gl::INT_SAMPLER_2D => "isampler2D",
+        gl::UNSIGNED_INT_SAMPLER_2D => "usampler2D",
+        gl::IMAGE_2D => "image2D",
+        _ => "<unknown type>",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(
+        name: &str,
+        gl_type: gl::types::GLenum,
+        location: gl::types::GLint,
+    ) -> ProgramResource {
+        ProgramResource {
+            name: name.into(),
+            gl_type,
+            array_size: 1,
+            location,
+        }
+    }
+
+    fn attrib(
+        name: &'static str,
+        location: usize,
+        shader_type: gl::types::GLenum,
+    ) -> VertexAttribInfo {
+        VertexAttribInfo {
+            name,
+            location,
+            shader_type,
+        }
+    }
+
+    fn reflection(inputs: Vec<ProgramResource>) -> ProgramReflection {
+        ProgramReflection {
+            inputs,
+            outputs: Vec::new(),
+            uniforms: Vec::new(),
+            uniform_blocks: Vec::new(),
+            storage_blocks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn attributes_may_have_fewer_components() {
+        assert!(attribute_fits(gl::FLOAT_VEC3, gl::FLOAT_VEC4));
+        assert!(attribute_fits(gl::FLOAT, gl::FLOAT_VEC2));
+        assert!(attribute_fits(gl::INT_VEC2, gl::INT_VEC2));
+        assert!(!attribute_fits(gl::FLOAT_VEC4, gl::FLOAT_VEC3));
+    }
+
+    #[test]
+    fn attributes_must_match_component_kind() {
+        assert!(!attribute_fits(gl::FLOAT_VEC4, gl::INT_VEC4));
+        assert!(!attribute_fits(gl::INT, gl::UNSIGNED_INT));
+        assert!(!attribute_fits(gl::DOUBLE_VEC2, gl::FLOAT_VEC2));
+        assert!(!attribute_fits(gl::FLOAT_VEC4, gl::FLOAT_MAT4));
+    }
+
+    #[test]
+    fn matrix_inputs_take_one_location_per_column() {
+        let slots = input_slots(&input("model", gl::FLOAT_MAT4x3, 2));
+
+        assert_eq!(
+            slots.iter().map(|slot| (slot.name.as_str(), slot.location)).collect::<Vec<_>>(),
+            vec![("model[0]", 2), ("model[1]", 3), ("model[2]", 4), ("model[3]", 5)]
+        );
+        assert!(slots.iter().all(|slot| slot.gl_type == gl::FLOAT_VEC3));
+
+        let slots = input_slots(&input("model", gl::DOUBLE_MAT4, 0));
+        assert_eq!(
+            slots.iter().map(|slot| slot.location).collect::<Vec<_>>(),
+            vec![0, 2, 4, 6]
+        );
+    }
+
+    #[test]
+    fn array_inputs_take_one_location_per_element() {
+        let mut weights = input("weights[0]", gl::FLOAT, 1);
+        weights.array_size = 3;
+
+        assert_eq!(
+            input_slots(&weights)
+                .iter()
+                .map(|slot| (slot.name.as_str(), slot.location))
+                .collect::<Vec<_>>(),
+            vec![("weights[0]", 1), ("weights[1]", 2), ("weights[2]", 3)]
+        );
+    }
+
+    #[test]
+    fn instanced_layout_feeds_every_input() {
+        let reflection = reflection(vec![
+            input("position", gl::FLOAT_VEC3, 0),
+            input("color", gl::FLOAT_VEC4, 1),
+            input("model", gl::FLOAT_MAT4, 2),
+        ]);
+        let vertex = vec![
+            attrib("position", 0, gl::FLOAT_VEC3),
+            attrib("color", 1, gl::FLOAT_VEC3),
+        ];
+        let instance = (0..4)
+            .map(|column| attrib("model", 2 + column, gl::FLOAT_VEC4))
+            .collect::<Vec<VertexAttribInfo>>();
+
+        assert!(reflection.attrib_mismatches(&[vertex.clone(), instance]).is_empty());
+        // Without the instance data the matrix columns are not fed
+        assert_eq!(reflection.attrib_mismatches(&[vertex]).len(), 4);
+    }
+
+    #[test]
+    fn dmat3_columns_are_fed_by_dvec3_attributes() {
+        let reflection = reflection(vec![input("transform", gl::DOUBLE_MAT3, 0)]);
+        let columns = (0..3)
+            .map(|column| attrib("transform", 2 * column, gl::DOUBLE_VEC3))
+            .collect::<Vec<VertexAttribInfo>>();
+
+        assert!(reflection.attrib_mismatches(&[columns]).is_empty());
+    }
+}
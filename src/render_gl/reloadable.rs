@@ -0,0 +1,85 @@
+use crate::render_gl::shader::{Error, Program};
+use crate::resources::{LoadedResource, Resources};
+use gl;
+
+/// `Program` loaded from resources that rebuilds itself when one of its
+/// shader files (or their includes) changes on disk
+pub struct ReloadableProgram {
+    gl: gl::Gl,
+    name: String,
+    defines: Vec<(String, String)>,
+    program: Program,
+    // Files the last build read, successful or not, as they were then
+    files: Vec<LoadedResource>,
+}
+
+impl ReloadableProgram {
+    /// Loads program `name` like `Program::from_res`, SPIR-V stages included
+    pub fn from_res(gl: &gl::Gl, res: &Resources, name: &str) -> Result<ReloadableProgram, Error> {
+        ReloadableProgram::from_res_with_defines(gl, res, name, &[])
+    }
+
+    /// Like `from_res`, with `#define NAME VALUE` added to every stage,
+    /// also on every reload
+    pub fn from_res_with_defines(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<ReloadableProgram, Error> {
+        let (program, files) = res.record_loads(|| build(gl, res, name, defines));
+        let program = program?;
+
+        Ok(ReloadableProgram {
+            gl: gl.clone(),
+            name: name.into(),
+            defines: defines
+                .iter()
+                .map(|&(define, value)| (define.to_owned(), value.to_owned()))
+                .collect(),
+            program,
+            files,
+        })
+    }
+
+    /// The most recent program that built successfully
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Rebuilds the program if any file the last build read changed, cheap
+    /// enough to call once per frame. Returns whether the program was replaced.
+    /// When the new sources fail to build the old program stays in use and
+    /// the error is returned; the next attempt waits for a change to one of
+    /// the files the failed build read.
+    pub fn poll(&mut self, res: &Resources) -> Result<bool, Error> {
+        if !self.files.iter().any(|file| res.is_modified(file)) {
+            return Ok(false);
+        }
+
+        let defines = self
+            .defines
+            .iter()
+            .map(|(define, value)| (define.as_str(), value.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+
+        let (program, files) = res.record_loads(|| build(&self.gl, res, &self.name, &defines));
+        self.files = files;
+        self.program = program?;
+        Ok(true)
+    }
+}
+
+/// Without defines the program may use SPIR-V stages, which defines can't change
+fn build(
+    gl: &gl::Gl,
+    res: &Resources,
+    name: &str,
+    defines: &[(&str, &str)],
+) -> Result<Program, Error> {
+    if defines.is_empty() {
+        Program::from_res(gl, res, name)
+    } else {
+        Program::from_res_with_defines(gl, res, name, defines)
+    }
+}
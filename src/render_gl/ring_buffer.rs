@@ -0,0 +1,147 @@
+use crate::render_gl::buffer::{Buffer, BufferType, Error};
+use crate::render_gl::data::Pod;
+use gl;
+
+/// How long to block in a single `ClientWaitSync` call before trying again
+const FENCE_TIMEOUT_NS: gl::types::GLuint64 = 1_000_000_000;
+
+/// Persistently mapped buffer split into `regions` regions of `region_len`
+/// elements each. The CPU writes one region per frame while the GPU is still
+/// reading the previous ones, a fence per region keeps the two apart.
+///
+/// ```ignore
+/// let region = ring.current_region_mut();   // waits until the GPU is done with it
+/// region[..sprites.len()].copy_from_slice(&sprites);
+/// // ... draw from `ring.current_offset()` ...
+/// ring.finish_region();                     // fences the draws and moves on
+/// ```
+pub struct RingBuffer<B, T>
+where
+    B: BufferType,
+    T: Pod,
+{
+    gl: gl::Gl,
+    buffer: Buffer<B>,
+    ptr: *mut T,
+    region_len: usize,
+    current: usize,
+    fences: Vec<gl::types::GLsync>,
+}
+
+impl<B, T> RingBuffer<B, T>
+where
+    B: BufferType,
+    T: Pod,
+{
+    pub fn new(gl: &gl::Gl, region_len: usize, regions: usize) -> Result<RingBuffer<B, T>, Error> {
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+
+        let buffer = Buffer::<B>::new(gl);
+        buffer.bind();
+        buffer.storage::<T>(region_len * regions, flags);
+        buffer.unbind();
+
+        // Mapping access has to match the storage flags
+        let ptr = buffer.map_range_ptr::<T>(0, region_len * regions, flags)?;
+
+        Ok(RingBuffer {
+            gl: gl.clone(),
+            buffer,
+            ptr,
+            region_len,
+            current: 0,
+            fences: vec![::std::ptr::null(); regions],
+        })
+    }
+
+    pub fn buffer(&self) -> &Buffer<B> {
+        &self.buffer
+    }
+
+    /// Number of elements in each region
+    pub fn region_len(&self) -> usize {
+        self.region_len
+    }
+
+    /// Number of regions the buffer is split into
+    pub fn regions(&self) -> usize {
+        self.fences.len()
+    }
+
+    /// Index of the region currently written to
+    pub fn current_region(&self) -> usize {
+        self.current
+    }
+
+    /// Offset of the current region in elements, e.g. the `first`
+    /// vertex to draw from
+    pub fn current_offset(&self) -> usize {
+        self.current * self.region_len
+    }
+
+    /// Offset of the current region in bytes, e.g. for `bind_range`
+    pub fn current_byte_offset(&self) -> usize {
+        self.current_offset() * ::std::mem::size_of::<T>()
+    }
+
+    /// Waits until the GPU is done reading the current region
+    /// and returns it for writing
+    pub fn current_region_mut(&mut self) -> &mut [T] {
+        self.wait_for_region(self.current);
+
+        unsafe {
+            ::std::slice::from_raw_parts_mut(self.ptr.add(self.current_offset()), self.region_len)
+        }
+    }
+
+    /// Fences every command issued so far against the current region
+    /// and moves on to the next one. Call after the draws reading
+    /// the region have been issued.
+    pub fn finish_region(&mut self) {
+        unsafe {
+            self.fences[self.current] = self.gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+        }
+        self.current = (self.current + 1) % self.fences.len();
+    }
+
+    fn wait_for_region(&mut self, region: usize) {
+        let fence = self.fences[region];
+        if fence.is_null() {
+            return;
+        }
+
+        loop {
+            let result = unsafe {
+                self.gl
+                    .ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, FENCE_TIMEOUT_NS)
+            };
+
+            match result {
+                gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => break,
+                gl::WAIT_FAILED => panic!("Failed to wait for ring buffer region {}", region),
+                _ => {} // gl::TIMEOUT_EXPIRED, keep waiting
+            }
+        }
+
+        unsafe {
+            self.gl.DeleteSync(fence);
+        }
+        self.fences[region] = ::std::ptr::null();
+    }
+}
+
+impl<B, T> Drop for RingBuffer<B, T>
+where
+    B: BufferType,
+    T: Pod,
+{
+    fn drop(&mut self) {
+        unsafe {
+            for fence in self.fences.iter().filter(|fence| !fence.is_null()) {
+                self.gl.DeleteSync(*fence);
+            }
+        }
+
+        self.buffer.unmap();
+    }
+}
@@ -1,6 +1,16 @@
+use crate::render_gl::capabilities;
+use crate::render_gl::data::{VertexAttribInfo, VertexAttribPointers};
+use crate::render_gl::diagnostics::{self, Diagnostic, Severity};
+use crate::render_gl::preprocessor::{self, PreprocessedSource};
+use crate::render_gl::program_cache::ProgramCache;
+use crate::render_gl::reflection::ProgramReflection;
+use crate::render_gl::spirv::Specialization;
+use crate::render_gl::uniform::Uniform;
 use crate::resources::{self, Resources};
 use gl;
 use std;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use thiserror::Error;
 
@@ -16,41 +26,180 @@ pub enum Error {
     #[error("Can not determine shader type for resource {}", name)]
     CanNotDetermineShaderTypeForResource { name: String },
 
-    #[error("Failed to compile shader {}: {}", name, message)]
-    CompileError { name: String, message: String },
+    #[error("No shader stages found for program {}", name)]
+    NoShaderStages { name: String },
 
-    #[error("Failed to link program {}: {}", name, message)]
-    LinkError { name: String, message: String },
+    #[error("Unknown shader stage {} in {} at line {}", stage, name, line)]
+    UnknownShaderStage {
+        name: String,
+        line: usize,
+        stage: String,
+    },
+
+    #[error("Shader stage {} appears more than once in {}", stage, name)]
+    DuplicateShaderStage { name: String, stage: String },
+
+    #[error("Include cycle in shader {}: {}", name, chain)]
+    IncludeCycle { name: String, chain: String },
+
+    #[error("Malformed #include in {} at line {}, expected #include \"path\"", name, line)]
+    MalformedInclude { name: String, line: usize },
+
+    #[error("Define {} for shader {} contains a nul or line break", define, name)]
+    InvalidDefine { name: String, define: String },
+
+    #[error("Failed to compile shader {}:\n{}", name, diagnostics::render(.diagnostics))]
+    CompileError {
+        name: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+
+    #[error("Failed to link program {}:\n{}", name, diagnostics::render(.diagnostics))]
+    LinkError {
+        name: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+
+    #[error("Program {} has no active uniform {}", program, name)]
+    UnknownUniform {
+        program: gl::types::GLuint,
+        name: String,
+    },
+
+    #[error("Vertex {} does not match the inputs of program {}:\n  {}", vertex, program, .mismatches.join("\n  "))]
+    VertexInputMismatch {
+        program: gl::types::GLuint,
+        vertex: String,
+        mismatches: Vec<String>,
+    },
+}
+
+impl Error {
+    /// `LinkError` for program `name` failing with info log `log`
+    pub(crate) fn link(name: &str, log: &str) -> Error {
+        Error::LinkError {
+            name: name.into(),
+            diagnostics: diagnostics::parse_info_log(log, None, Severity::Error),
+        }
+    }
 }
 
+/// Shader resource file extensions and the stage each one compiles to,
+/// in pipeline order
+const SHADER_KINDS: [(&str, gl::types::GLenum); 6] = [
+    (".vert", gl::VERTEX_SHADER),
+    (".tesc", gl::TESS_CONTROL_SHADER),
+    (".tese", gl::TESS_EVALUATION_SHADER),
+    (".geom", gl::GEOMETRY_SHADER),
+    (".frag", gl::FRAGMENT_SHADER),
+    (".comp", gl::COMPUTE_SHADER),
+];
+
+/// `#pragma stage NAME` names of the sections of a `.glsl` file
+const STAGE_PRAGMAS: [(&str, gl::types::GLenum); 6] = [
+    ("vertex", gl::VERTEX_SHADER),
+    ("tess_control", gl::TESS_CONTROL_SHADER),
+    ("tess_evaluation", gl::TESS_EVALUATION_SHADER),
+    ("geometry", gl::GEOMETRY_SHADER),
+    ("fragment", gl::FRAGMENT_SHADER),
+    ("compute", gl::COMPUTE_SHADER),
+];
+
 pub struct Program {
     gl: gl::Gl,
     id: gl::types::GLuint,
+    // Uniform name -> location, -1 for names that are not active uniforms
+    uniform_locations: RefCell<HashMap<String, gl::types::GLint>>,
+    // Resources the shaders were loaded from, includes too
+    resources: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Program {
+    /// Loads every stage of program `name`: either the single file
+    /// `{name}.glsl` split at its `#pragma stage` lines, or whichever
+    /// of `{name}.vert`, `{name}.frag`, ... exist. When the context
+    /// supports SPIR-V, stages with a precompiled `{name}.vert.spv`, ...
+    /// next to their source are loaded from it instead.
     pub fn from_res(gl: &gl::Gl, res: &Resources, name: &str) -> Result<Program, Error> {
-        const POSSIBLE_EXT: [&str; 2] = [".vert", ".frag"];
+        Program::from_res_with_specialization(gl, res, name, &Specialization::new())
+    }
 
-        let resource_names = POSSIBLE_EXT
-            .iter()
-            .map(|file_extension| format!("{}{}", name, file_extension))
-            .collect::<Vec<String>>();
+    /// Like `from_res`, with `#define NAME VALUE` added to every stage.
+    /// Defines can't change a SPIR-V binary, so this always compiles the GLSL.
+    pub fn from_res_with_defines(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<Program, Error> {
+        Program::build(gl, res, name, defines, None)
+    }
 
-        let shaders = resource_names
+    /// Like `from_res`, setting the specialization constants of the stages
+    /// loaded from SPIR-V. Stages compiled from GLSL have none.
+    pub fn from_res_with_specialization(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        specialization: &Specialization,
+    ) -> Result<Program, Error> {
+        Program::build(gl, res, name, &[], Some(specialization))
+    }
+
+    /// Compiles and links every stage of `name`, from SPIR-V where a
+    /// `specialization` is given and the context supports it
+    fn build(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        defines: &[(&str, &str)],
+        specialization: Option<&Specialization>,
+    ) -> Result<Program, Error> {
+        let stages = preprocessed_stages(res, name, defines)?;
+        let specialization = specialization.filter(|_| capabilities::has_spirv(gl));
+
+        let shaders = stages
             .iter()
-            .map(|resource_name| Shader::from_res(gl, res, resource_name))
+            .map(|(stage_name, kind, source)| {
+                let spirv_name = spirv_name(name, *kind);
+                match specialization {
+                    Some(specialization) if res.exists(&spirv_name) => {
+                        Shader::from_spirv_res(gl, res, &spirv_name, specialization)
+                    }
+                    _ => Shader::from_preprocessed(gl, stage_name, *kind, source),
+                }
+            })
             .collect::<Result<Vec<Shader>, Error>>()?; // Report just the 1st error encountered (tricky tricky)
                                                        // @NOTE: on `collect()`: When we have a bunch of `Result<T, E>` items we can collect them
                                                        // into a `Result<Vec<T>, E> which will contain a first encountered error OR a list of unwrapped values
 
-        Program::from_shaders(gl, &shaders[..]).map_err(|message| Error::LinkError {
-            name: name.into(),
-            message,
-        })
+        Program::from_shaders(gl, &shaders[..]).map_err(|log| Error::link(name, &log))
     }
 
     pub fn from_shaders(gl: &gl::Gl, shaders: &[Shader]) -> Result<Program, String> {
+        Program::link(gl, shaders, false)
+    }
+
+    /// Like `from_res_with_defines`, loading the linked program from `cache`
+    /// when it was built before with the same sources and driver
+    pub fn from_res_cached(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        defines: &[(&str, &str)],
+        cache: &ProgramCache,
+    ) -> Result<Program, Error> {
+        cache.load(gl, res, name, defines)
+    }
+
+    /// Links `shaders`, asking the driver to keep the program binary
+    /// around for `binary` when `binary_retrievable` is set
+    pub(crate) fn link(
+        gl: &gl::Gl,
+        shaders: &[Shader],
+        binary_retrievable: bool,
+    ) -> Result<Program, String> {
         let program_id = unsafe { gl.CreateProgram() };
 
         // Attach all shaders to this program
@@ -58,6 +207,16 @@ impl Program {
             unsafe { gl.AttachShader(program_id, shader.id()) }
         }
 
+        if binary_retrievable {
+            unsafe {
+                gl.ProgramParameteri(
+                    program_id,
+                    gl::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    gl::TRUE as gl::types::GLint,
+                );
+            }
+        }
+
         // Link the program
         unsafe {
             gl.LinkProgram(program_id);
@@ -69,25 +228,13 @@ impl Program {
             gl.GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
         }
 
-        if success == 0 {
-            dbg!("Failed to link program");
-            let mut len: gl::types::GLint = 0;
-            unsafe {
-                gl.GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut len);
-            }
-
-            let error = create_whitespace_cstring_with_len(len as usize);
+        let log = program_info_log(gl, program_id);
 
+        if success == 0 {
             unsafe {
-                gl.GetProgramInfoLog(
-                    program_id,
-                    len,
-                    std::ptr::null_mut(),
-                    error.as_ptr() as *mut gl::types::GLchar,
-                );
+                gl.DeleteProgram(program_id);
             }
-
-            return Err(error.to_string_lossy().into_owned());
+            return Err(log);
         }
 
         // Detach shaders from program so that their Drop impl can
@@ -98,12 +245,97 @@ impl Program {
             }
         }
 
+        let mut resources: Vec<String> = Vec::new();
+        for resource in shaders.iter().flat_map(|shader| shader.resources()) {
+            if !resources.contains(resource) {
+                resources.push(resource.clone());
+            }
+        }
+
+        // Warnings of the stages, then the linker's
+        let mut diagnostics = shaders
+            .iter()
+            .flat_map(|shader| shader.diagnostics().iter().cloned())
+            .collect::<Vec<Diagnostic>>();
+        diagnostics.extend(diagnostics::parse_info_log(&log, None, Severity::Warning));
+
         Ok(Program {
             gl: gl.clone(),
             id: program_id,
+            uniform_locations: RefCell::new(HashMap::new()),
+            resources,
+            diagnostics,
+        })
+    }
+
+    /// Loads a program saved with `binary`. `None` when the driver
+    /// rejects it, e.g. after a driver update.
+    pub(crate) fn from_binary(
+        gl: &gl::Gl,
+        format: gl::types::GLenum,
+        binary: &[u8],
+        resources: Vec<String>,
+    ) -> Option<Program> {
+        let program_id = unsafe { gl.CreateProgram() };
+
+        let mut success: gl::types::GLint = 0;
+        unsafe {
+            gl.ProgramBinary(
+                program_id,
+                format,
+                binary.as_ptr() as *const gl::types::GLvoid,
+                binary.len() as gl::types::GLsizei,
+            );
+            gl.GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
+        }
+
+        if success == 0 {
+            unsafe {
+                gl.DeleteProgram(program_id);
+            }
+            return None;
+        }
+
+        Some(Program {
+            gl: gl.clone(),
+            id: program_id,
+            uniform_locations: RefCell::new(HashMap::new()),
+            resources,
+            diagnostics: Vec::new(),
         })
     }
 
+    /// Driver specific binary format and blob of the linked program,
+    /// `None` when the driver has none to give
+    pub(crate) fn binary(&self) -> Option<(gl::types::GLenum, Vec<u8>)> {
+        let mut len: gl::types::GLint = 0;
+        unsafe {
+            self.gl.GetProgramiv(self.id, gl::PROGRAM_BINARY_LENGTH, &mut len);
+        }
+        if len <= 0 {
+            return None;
+        }
+
+        let mut binary: Vec<u8> = vec![0; len as usize];
+        let mut written: gl::types::GLsizei = 0;
+        let mut format: gl::types::GLenum = 0;
+        unsafe {
+            self.gl.GetProgramBinary(
+                self.id,
+                len,
+                &mut written,
+                &mut format,
+                binary.as_mut_ptr() as *mut gl::types::GLvoid,
+            );
+        }
+        if written <= 0 {
+            return None;
+        }
+
+        binary.truncate(written as usize);
+        Some((format, binary))
+    }
+
     pub fn id(&self) -> gl::types::GLuint {
         self.id
     }
@@ -111,6 +343,108 @@ impl Program {
     pub fn set_used(&self) {
         unsafe { self.gl.UseProgram(self.id) }
     }
+
+    /// Resources the program was built from, including `#include`d ones.
+    /// Empty for programs built from sources.
+    pub fn resources(&self) -> &[String] {
+        &self.resources
+    }
+
+    /// Warnings and notes the compiler and linker reported while building
+    /// the program. Empty for programs loaded from a binary.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Location of uniform `name`, looked up once and cached.
+    /// Array elements and struct members use their GLSL names, e.g. `lights[2].color`.
+    pub fn uniform_location(&self, name: &str) -> Result<gl::types::GLint, Error> {
+        let cached = self.uniform_locations.borrow().get(name).copied();
+
+        let location = match cached {
+            Some(location) => location,
+            None => {
+                let location = match CString::new(name) {
+                    Ok(c_name) => unsafe { self.gl.GetUniformLocation(self.id, c_name.as_ptr()) },
+                    Err(_) => -1,
+                };
+                self.uniform_locations
+                    .borrow_mut()
+                    .insert(name.to_owned(), location);
+                location
+            }
+        };
+
+        if location < 0 {
+            return Err(Error::UnknownUniform {
+                program: self.id,
+                name: name.into(),
+            });
+        }
+
+        Ok(location)
+    }
+
+    /// Sets uniform `name`, the program does not need to be in use
+    pub fn set_uniform<T: Uniform>(&self, name: &str, value: &T) -> Result<(), Error> {
+        self.set_uniform_array(name, ::std::slice::from_ref(value))
+    }
+
+    /// Sets consecutive elements of uniform array `name`, starting from the
+    /// first one or from the one named, e.g. `offsets[4]`
+    pub fn set_uniform_array<T: Uniform>(&self, name: &str, values: &[T]) -> Result<(), Error> {
+        let location = self.uniform_location(name)?;
+        unsafe {
+            T::set_uniforms(&self.gl, self.id, location, values);
+        }
+        Ok(())
+    }
+
+    /// Active inputs, outputs, uniforms and blocks of the linked program
+    pub fn reflect(&self) -> ProgramReflection {
+        ProgramReflection::from_program(&self.gl, self.id)
+    }
+
+    /// Checks that vertex `V` feeds every vertex shader input, with the
+    /// types the shader declares them as
+    pub fn check_vertex_inputs<V: VertexAttribPointers>(&self) -> Result<(), Error> {
+        let mismatches = self.reflect().vertex_mismatches::<V>();
+
+        if !mismatches.is_empty() {
+            return Err(Error::VertexInputMismatch {
+                program: self.id,
+                vertex: ::std::any::type_name::<V>().into(),
+                mismatches,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Like `check_vertex_inputs`, for attributes fed from several vertex
+    /// structs: `&[Vertex::vertex_attribs(), Instance::vertex_attribs()]`
+    pub fn check_vertex_attribs(
+        &self,
+        attrib_lists: &[Vec<VertexAttribInfo>],
+    ) -> Result<(), Error> {
+        let mismatches = self.reflect().attrib_mismatches(attrib_lists);
+
+        if !mismatches.is_empty() {
+            return Err(Error::VertexInputMismatch {
+                program: self.id,
+                vertex: "layout".into(),
+                mismatches,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Makes sampler uniform `name` read from texture unit `unit`
+    /// (the one activated with `gl::TEXTURE0 + unit`)
+    pub fn set_sampler(&self, name: &str, unit: u32) -> Result<(), Error> {
+        self.set_uniform(name, &(unit as i32))
+    }
 }
 
 impl Drop for Program {
@@ -124,37 +458,152 @@ impl Drop for Program {
 pub struct Shader {
     gl: gl::Gl,
     id: gl::types::GLuint,
+    resources: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Shader {
     pub fn from_res(gl: &gl::Gl, res: &Resources, name: &str) -> Result<Shader, Error> {
-        const POSSIBLE_EXT: [(&str, gl::types::GLenum); 2] =
-            [(".vert", gl::VERTEX_SHADER), (".frag", gl::FRAGMENT_SHADER)];
+        Shader::from_res_with_defines(gl, res, name, &[])
+    }
 
-        let shader_kind = POSSIBLE_EXT
+    /// Loads and compiles shader resource `name`, resolving its `#include`s
+    /// and adding `#define NAME VALUE` for each of `defines`. Compile errors
+    /// point at the original file and line.
+    pub fn from_res_with_defines(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<Shader, Error> {
+        let shader_kind = SHADER_KINDS
             .iter()
             .find(|&&(file_extension, _)| name.ends_with(file_extension))
             .map(|&(_, kind)| kind)
             .ok_or_else(|| Error::CanNotDetermineShaderTypeForResource { name: name.into() })?;
 
-        let source = res.load_cstring(name).map_err(|e| Error::ResourceLoad {
+        let source = preprocessor::preprocess(res, name, defines)?;
+
+        Shader::from_preprocessed(gl, name, shader_kind, &source)
+    }
+
+    /// Compiles already preprocessed resource `name`
+    pub fn from_preprocessed(
+        gl: &gl::Gl,
+        name: &str,
+        kind: gl::types::GLenum,
+        source: &PreprocessedSource,
+    ) -> Result<Shader, Error> {
+        let (id, log) = shader_from_source(gl, &source.source, kind).map_err(|log| {
+            Error::CompileError {
+                name: name.into(),
+                diagnostics: diagnostics::parse_info_log(&log, Some(source), Severity::Error),
+            }
+        })?;
+
+        Ok(Shader {
+            gl: gl.clone(),
+            id,
+            resources: source
+                .line_map
+                .files()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            diagnostics: diagnostics::parse_info_log(&log, Some(source), Severity::Warning),
+        })
+    }
+
+    /// Loads precompiled SPIR-V resource `name`, e.g. `shaders/triangle.vert.spv`,
+    /// with entry point `main`. The extension before `.spv` gives the stage.
+    pub fn from_spirv_res(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        specialization: &Specialization,
+    ) -> Result<Shader, Error> {
+        let shader_kind = SHADER_KINDS
+            .iter()
+            .find(|&&(file_extension, _)| name.ends_with(&format!("{}.spv", file_extension)))
+            .map(|&(_, kind)| kind)
+            .ok_or_else(|| Error::CanNotDetermineShaderTypeForResource { name: name.into() })?;
+
+        let binary = res.load_bytes(name).map_err(|e| Error::ResourceLoad {
             name: name.into(),
             inner: e,
         })?;
 
-        Shader::from_source(gl, &source, shader_kind).map_err(|message| Error::CompileError {
-            name: name.into(),
-            message,
+        let mut shader = Shader::from_spirv(gl, &binary, shader_kind, "main", specialization)
+            .map_err(|log| Error::CompileError {
+                name: name.into(),
+                diagnostics: diagnostics::parse_info_log(&log, None, Severity::Error),
+            })?;
+        shader.resources = vec![name.to_owned()];
+
+        Ok(shader)
+    }
+
+    /// Loads a SPIR-V module and specializes its `entry_point`,
+    /// returning the info log on failure. Needs `capabilities::has_spirv`.
+    pub fn from_spirv(
+        gl: &gl::Gl,
+        binary: &[u8],
+        kind: gl::types::GLenum,
+        entry_point: &str,
+        specialization: &Specialization,
+    ) -> Result<Shader, String> {
+        // SPIR-V is a stream of 32 bit words
+        if !binary.len().is_multiple_of(4) {
+            return Err(format!(
+                "error: SPIR-V binary is {} bytes, not a whole number of words",
+                binary.len()
+            ));
+        }
+        let entry_point = CString::new(entry_point)
+            .map_err(|_| "error: SPIR-V entry point name contains nul".to_string())?;
+
+        let id = unsafe { gl.CreateShader(kind) };
+
+        unsafe {
+            gl.ShaderBinary(
+                1,
+                &id,
+                gl::SHADER_BINARY_FORMAT_SPIR_V,
+                binary.as_ptr() as *const gl::types::GLvoid,
+                binary.len() as gl::types::GLsizei,
+            );
+            gl.SpecializeShader(
+                id,
+                entry_point.as_ptr(),
+                specialization.constant_ids().len() as gl::types::GLuint,
+                specialization.constant_ids().as_ptr(),
+                specialization.values().as_ptr(),
+            );
+        }
+
+        let log = compile_status(gl, id)?;
+
+        Ok(Shader {
+            gl: gl.clone(),
+            id,
+            resources: Vec::new(),
+            diagnostics: diagnostics::parse_info_log(&log, None, Severity::Warning),
         })
     }
 
+    /// Compiles `source`, returning the info log on failure
     pub fn from_source(
         gl: &gl::Gl,
         source: &CStr,
         kind: gl::types::GLenum,
     ) -> Result<Shader, String> {
-        let id = shader_from_source(gl, source, kind)?;
-        Ok(Shader { gl: gl.clone(), id })
+        let (id, log) = shader_from_source(gl, source, kind)?;
+        Ok(Shader {
+            gl: gl.clone(),
+            id,
+            resources: Vec::new(),
+            diagnostics: diagnostics::parse_info_log(&log, None, Severity::Warning),
+        })
     }
 
     pub fn from_vert_source(gl: &gl::Gl, source: &CStr) -> Result<Shader, String> {
@@ -165,9 +614,35 @@ impl Shader {
         Shader::from_source(gl, source, gl::FRAGMENT_SHADER)
     }
 
+    pub fn from_geom_source(gl: &gl::Gl, source: &CStr) -> Result<Shader, String> {
+        Shader::from_source(gl, source, gl::GEOMETRY_SHADER)
+    }
+
+    pub fn from_tess_control_source(gl: &gl::Gl, source: &CStr) -> Result<Shader, String> {
+        Shader::from_source(gl, source, gl::TESS_CONTROL_SHADER)
+    }
+
+    pub fn from_tess_evaluation_source(gl: &gl::Gl, source: &CStr) -> Result<Shader, String> {
+        Shader::from_source(gl, source, gl::TESS_EVALUATION_SHADER)
+    }
+
+    pub fn from_comp_source(gl: &gl::Gl, source: &CStr) -> Result<Shader, String> {
+        Shader::from_source(gl, source, gl::COMPUTE_SHADER)
+    }
+
     pub fn id(&self) -> gl::types::GLuint {
         self.id
     }
+
+    /// Resources the shader was loaded from, the main file first
+    pub fn resources(&self) -> &[String] {
+        &self.resources
+    }
+
+    /// Warnings and notes the compiler reported for the shader
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
 }
 
 impl Drop for Shader {
@@ -178,11 +653,95 @@ impl Drop for Shader {
     }
 }
 
+/// Preprocessed sources of every stage of program `name`, with a name
+/// for error messages and the shader kind. The stages come from
+/// `{name}.glsl` when it exists, otherwise from whichever of
+/// `{name}.vert`, `{name}.frag`, ... exist, so programs can link just
+/// `.comp` or `.vert` + `.geom` + `.frag`.
+pub(crate) fn preprocessed_stages(
+    res: &Resources,
+    name: &str,
+    defines: &[(&str, &str)],
+) -> Result<Vec<(String, gl::types::GLenum, PreprocessedSource)>, Error> {
+    let combined_name = format!("{}.glsl", name);
+    if res.exists(&combined_name) {
+        return split_combined_source(res, &combined_name, defines);
+    }
+
+    let stages = SHADER_KINDS
+        .iter()
+        .map(|&(file_extension, kind)| (format!("{}{}", name, file_extension), kind))
+        .filter(|(resource_name, _)| res.exists(resource_name))
+        .map(|(resource_name, kind)| {
+            let source = preprocessor::preprocess(res, &resource_name, defines)?;
+            Ok((resource_name, kind, source))
+        })
+        .collect::<Result<Vec<(String, gl::types::GLenum, PreprocessedSource)>, Error>>()?;
+
+    if stages.is_empty() {
+        return Err(Error::NoShaderStages { name: name.into() });
+    }
+
+    Ok(stages)
+}
+
+/// Precompiled SPIR-V resource of the `kind` stage of program `name`,
+/// e.g. `shaders/triangle.vert.spv`, the same for split and combined sources
+fn spirv_name(name: &str, kind: gl::types::GLenum) -> String {
+    let file_extension = SHADER_KINDS
+        .iter()
+        .find(|&&(_, other_kind)| other_kind == kind)
+        .map(|&(file_extension, _)| file_extension)
+        .expect("Every shader kind has a file extension");
+    format!("{}{}.spv", name, file_extension)
+}
+
+/// Preprocesses `.glsl` resource `name` as a whole, so includes and
+/// defines are shared, then splits it into its `#pragma stage` sections
+fn split_combined_source(
+    res: &Resources,
+    name: &str,
+    defines: &[(&str, &str)],
+) -> Result<Vec<(String, gl::types::GLenum, PreprocessedSource)>, Error> {
+    let combined = preprocessor::preprocess(res, name, defines)?;
+    let sections = preprocessor::split_stages(&combined);
+
+    if sections.is_empty() {
+        return Err(Error::NoShaderStages { name: name.into() });
+    }
+
+    let mut stages: Vec<(String, gl::types::GLenum, PreprocessedSource)> = Vec::new();
+    for section in sections {
+        let kind = STAGE_PRAGMAS
+            .iter()
+            .find(|&&(stage, _)| stage == section.stage)
+            .map(|&(_, kind)| kind)
+            .ok_or_else(|| Error::UnknownShaderStage {
+                name: section.pragma.file.clone(),
+                line: section.pragma.line,
+                stage: section.stage.clone(),
+            })?;
+
+        if stages.iter().any(|&(_, other_kind, _)| other_kind == kind) {
+            return Err(Error::DuplicateShaderStage {
+                name: name.into(),
+                stage: section.stage,
+            });
+        }
+
+        stages.push((format!("{} ({})", name, section.stage), kind, section.source));
+    }
+
+    Ok(stages)
+}
+
+/// Compiles `source`, returning the shader and its (possibly empty)
+/// info log, or the info log alone on failure
 fn shader_from_source(
     gl: &gl::Gl,
     source: &CStr,
     kind: gl::types::GLenum,
-) -> Result<gl::types::GLuint, String> {
+) -> Result<(gl::types::GLuint, String), String> {
     let id = unsafe { gl.CreateShader(kind) };
 
     unsafe {
@@ -190,45 +749,93 @@ fn shader_from_source(
         gl.CompileShader(id);
     }
 
+    let log = compile_status(gl, id)?;
+
+    Ok((id, log))
+}
+
+/// Info log of compiled (or specialized) shader `id`. On failure
+/// the shader is deleted and the log returned as the error.
+fn compile_status(gl: &gl::Gl, id: gl::types::GLuint) -> Result<String, String> {
     let mut success: gl::types::GLint = 1;
     unsafe {
         gl.GetShaderiv(id, gl::COMPILE_STATUS, &mut success);
     }
 
-    if success == 0 {
-        dbg!("Failed to compile shader");
-        // Failure to compile, let's get the error message
+    let log = shader_info_log(gl, id);
 
-        // 1. Get length of error message and create appropriate buffer & CString
-        let mut len: gl::types::GLint = 0;
+    if success == 0 {
         unsafe {
-            gl.GetShaderiv(id, gl::INFO_LOG_LENGTH, &mut len);
+            gl.DeleteShader(id);
         }
+        return Err(log);
+    }
 
-        let error = create_whitespace_cstring_with_len(len as usize);
+    Ok(log)
+}
 
-        // 2. Ask OpenGL to write the shader info log into our error value
-        unsafe {
-            gl.GetShaderInfoLog(
-                id,
-                len,
-                std::ptr::null_mut(),
-                error.as_ptr() as *mut gl::types::GLchar,
-            );
-        }
+fn shader_info_log(gl: &gl::Gl, id: gl::types::GLuint) -> String {
+    // 1. Get length of the message and create appropriate buffer & CString
+    let mut len: gl::types::GLint = 0;
+    unsafe {
+        gl.GetShaderiv(id, gl::INFO_LOG_LENGTH, &mut len);
+    }
+    if len <= 0 {
+        return String::new();
+    }
+
+    let log = create_whitespace_cstring_with_len(len as usize);
 
-        // And finally we can return the error
-        return Err(error.to_string_lossy().into_owned());
+    // 2. Ask OpenGL to write the shader info log into it
+    unsafe {
+        gl.GetShaderInfoLog(
+            id,
+            len,
+            std::ptr::null_mut(),
+            log.as_ptr() as *mut gl::types::GLchar,
+        );
     }
 
-    Ok(id)
+    cstring_to_log(log)
+}
+
+fn program_info_log(gl: &gl::Gl, id: gl::types::GLuint) -> String {
+    let mut len: gl::types::GLint = 0;
+    unsafe {
+        gl.GetProgramiv(id, gl::INFO_LOG_LENGTH, &mut len);
+    }
+    if len <= 0 {
+        return String::new();
+    }
+
+    let log = create_whitespace_cstring_with_len(len as usize);
+
+    unsafe {
+        gl.GetProgramInfoLog(
+            id,
+            len,
+            std::ptr::null_mut(),
+            log.as_ptr() as *mut gl::types::GLchar,
+        );
+    }
+
+    cstring_to_log(log)
+}
+
+/// The info log length counts GL's nul terminator, drop it and what follows
+fn cstring_to_log(log: CString) -> String {
+    let log = log.to_string_lossy();
+    match log.find('\0') {
+        Some(end) => log[..end].to_owned(),
+        None => log.into_owned(),
+    }
 }
 
 fn create_whitespace_cstring_with_len(len: usize) -> CString {
     // Allocate buffer of correct size
     let mut buffer: Vec<u8> = Vec::with_capacity(len + 1);
     // fill it with len spaces
-    buffer.extend([b'_'].iter().cycle().take(len));
+    buffer.extend([b' '].iter().cycle().take(len));
     // Convert buffer to CString (re-uses allocation and appends 0 at end)
     unsafe { CString::from_vec_unchecked(buffer) }
 }
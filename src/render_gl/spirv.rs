@@ -0,0 +1,66 @@
+/// A value a SPIR-V specialization constant can be set to, as the 32 bits
+/// `SpecializeShader` takes
+pub trait SpecializationValue {
+    fn to_bits(self) -> u32;
+}
+
+impl SpecializationValue for u32 {
+    fn to_bits(self) -> u32 {
+        self
+    }
+}
+
+impl SpecializationValue for i32 {
+    fn to_bits(self) -> u32 {
+        self as u32
+    }
+}
+
+impl SpecializationValue for f32 {
+    fn to_bits(self) -> u32 {
+        f32::to_bits(self)
+    }
+}
+
+impl SpecializationValue for bool {
+    fn to_bits(self) -> u32 {
+        self as u32
+    }
+}
+
+/// Values of the `layout (constant_id = N)` constants of a SPIR-V shader.
+/// Constants not set keep the default the shader declares.
+#[derive(Clone, Debug, Default)]
+pub struct Specialization {
+    constant_ids: Vec<u32>,
+    values: Vec<u32>,
+}
+
+impl Specialization {
+    pub fn new() -> Specialization {
+        Specialization::default()
+    }
+
+    /// Sets constant `constant_id`, replacing an earlier value
+    pub fn with<T: SpecializationValue>(mut self, constant_id: u32, value: T) -> Specialization {
+        let value = value.to_bits();
+
+        match self.constant_ids.iter().position(|&id| id == constant_id) {
+            Some(index) => self.values[index] = value,
+            None => {
+                self.constant_ids.push(constant_id);
+                self.values.push(value);
+            }
+        }
+
+        self
+    }
+
+    pub fn constant_ids(&self) -> &[u32] {
+        &self.constant_ids
+    }
+
+    pub fn values(&self) -> &[u32] {
+        &self.values
+    }
+}
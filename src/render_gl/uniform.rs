@@ -0,0 +1,83 @@
+use gl;
+use nalgebra as na;
+
+/// A type that can be the value of a `uniform` (or an element of a
+/// uniform array) set through `Program::set_uniform`.
+pub trait Uniform: Sized {
+    /// Sets `values.len()` consecutive array elements starting at `location`
+    /// of `program`, a single value is an array of one.
+    ///
+    /// # Safety
+    ///
+    /// `location` must be a location returned by `GetUniformLocation`
+    /// (or -1) for `program`.
+    unsafe fn set_uniforms(
+        gl: &gl::Gl,
+        program: gl::types::GLuint,
+        location: gl::types::GLint,
+        values: &[Self],
+    );
+}
+
+macro_rules! uniform {
+    ($t:ty, $component:ty, $setter:ident) => {
+        impl Uniform for $t {
+            unsafe fn set_uniforms(
+                gl: &gl::Gl,
+                program: gl::types::GLuint,
+                location: gl::types::GLint,
+                values: &[Self],
+            ) {
+                gl.$setter(
+                    program,
+                    location,
+                    values.len() as gl::types::GLsizei,
+                    values.as_ptr() as *const $component,
+                );
+            }
+        }
+    };
+}
+
+macro_rules! matrix_uniform {
+    ($t:ty, $setter:ident) => {
+        impl Uniform for $t {
+            unsafe fn set_uniforms(
+                gl: &gl::Gl,
+                program: gl::types::GLuint,
+                location: gl::types::GLint,
+                values: &[Self],
+            ) {
+                gl.$setter(
+                    program,
+                    location,
+                    values.len() as gl::types::GLsizei,
+                    gl::FALSE, // nalgebra matrices are already column major
+                    values.as_ptr() as *const f32,
+                );
+            }
+        }
+    };
+}
+
+uniform!(f32, f32, ProgramUniform1fv);
+uniform!(na::Vector2<f32>, f32, ProgramUniform2fv);
+uniform!(na::Vector3<f32>, f32, ProgramUniform3fv);
+uniform!(na::Vector4<f32>, f32, ProgramUniform4fv);
+
+uniform!(i32, i32, ProgramUniform1iv);
+uniform!(na::Vector2<i32>, i32, ProgramUniform2iv);
+uniform!(na::Vector3<i32>, i32, ProgramUniform3iv);
+uniform!(na::Vector4<i32>, i32, ProgramUniform4iv);
+
+uniform!(u32, u32, ProgramUniform1uiv);
+uniform!(na::Vector2<u32>, u32, ProgramUniform2uiv);
+uniform!(na::Vector3<u32>, u32, ProgramUniform3uiv);
+uniform!(na::Vector4<u32>, u32, ProgramUniform4uiv);
+
+uniform!(na::Point2<f32>, f32, ProgramUniform2fv);
+uniform!(na::Point3<f32>, f32, ProgramUniform3fv);
+
+matrix_uniform!(na::Matrix2<f32>, ProgramUniformMatrix2fv);
+matrix_uniform!(na::Matrix3<f32>, ProgramUniformMatrix3fv);
+matrix_uniform!(na::Matrix4<f32>, ProgramUniformMatrix4fv);
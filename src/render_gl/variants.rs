@@ -0,0 +1,146 @@
+use crate::render_gl::program_cache::ProgramCache;
+use crate::render_gl::shader::{Error, Program};
+use crate::resources::Resources;
+use gl;
+use std::collections::{BTreeMap, HashMap};
+
+/// Feature flags selecting one variant of a shader, each one becomes a
+/// `#define`. Flags are ordered by name, so the order they are added in
+/// does not make a different variant.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Features {
+    defines: BTreeMap<String, String>,
+}
+
+impl Features {
+    pub fn new() -> Features {
+        Features::default()
+    }
+
+    /// Boolean flag, `#define NAME 1`
+    pub fn with(self, name: &str) -> Features {
+        self.with_value(name, 1)
+    }
+
+    /// Enum flag, `#define NAME VALUE`, e.g. `SHADOW_QUALITY` 2
+    pub fn with_value<T: ToString>(mut self, name: &str, value: T) -> Features {
+        self.defines.insert(name.into(), value.to_string());
+        self
+    }
+
+    /// Boolean flag that is only defined when `enabled`, so shaders
+    /// can test it with `#ifdef`
+    pub fn with_if(self, name: &str, enabled: bool) -> Features {
+        if enabled {
+            self.with(name)
+        } else {
+            self
+        }
+    }
+
+    pub fn defines(&self) -> Vec<(&str, &str)> {
+        self.defines
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+}
+
+/// Every variant of one shader, compiled the first time it is asked for
+pub struct ProgramVariants {
+    gl: gl::Gl,
+    name: String,
+    cache: Option<ProgramCache>,
+    variants: HashMap<Features, Program>,
+}
+
+impl ProgramVariants {
+    /// Variants of the stages of resource `name`, as `Program::from_res` finds them
+    pub fn new(gl: &gl::Gl, name: &str) -> ProgramVariants {
+        ProgramVariants {
+            gl: gl.clone(),
+            name: name.into(),
+            cache: None,
+            variants: HashMap::new(),
+        }
+    }
+
+    /// Like `new`, loading and saving the variants' binaries in `cache`
+    pub fn with_cache(gl: &gl::Gl, name: &str, cache: ProgramCache) -> ProgramVariants {
+        ProgramVariants {
+            cache: Some(cache),
+            ..ProgramVariants::new(gl, name)
+        }
+    }
+
+    /// The variant for `features`, built now if it is the first request for it.
+    /// Failed builds are not remembered, the next request tries again.
+    pub fn get(&mut self, res: &Resources, features: &Features) -> Result<&Program, Error> {
+        if !self.variants.contains_key(features) {
+            let defines = features.defines();
+            let program = match &self.cache {
+                Some(cache) => {
+                    Program::from_res_cached(&self.gl, res, &self.name, &defines, cache)?
+                }
+                None => Program::from_res_with_defines(&self.gl, res, &self.name, &defines)?,
+            };
+            self.variants.insert(features.clone(), program);
+        }
+
+        Ok(&self.variants[features])
+    }
+
+    /// Number of variants built so far
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    /// Drops every built variant, e.g. after editing the shader sources
+    pub fn clear(&mut self) {
+        self.variants.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash(features: &Features) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        features.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn flag_order_does_not_change_the_variant() {
+        let a = Features::new().with("SHADOWS").with_value("LIGHTS", 4);
+        let b = Features::new().with_value("LIGHTS", 4).with("SHADOWS");
+
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_eq!(a.defines(), vec![("LIGHTS", "4"), ("SHADOWS", "1")]);
+        assert_eq!(a.defines(), b.defines());
+    }
+
+    #[test]
+    fn different_values_are_different_variants() {
+        let low = Features::new().with_value("SHADOW_QUALITY", 1);
+        let high = Features::new().with_value("SHADOW_QUALITY", 2);
+
+        assert_ne!(low, high);
+    }
+
+    #[test]
+    fn with_if_false_leaves_the_flag_undefined() {
+        let features = Features::new().with_if("SHADOWS", false).with_if("FOG", true);
+
+        assert_eq!(features.defines(), vec![("FOG", "1")]);
+        assert_eq!(features, Features::new().with("FOG"));
+    }
+}
@@ -1,7 +1,9 @@
+use std::cell::RefCell;
 use std::ffi;
 use std::fs;
 use std::io::{self, Read};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -22,6 +24,9 @@ impl From<io::Error> for Error {
 
 pub struct Resources {
     root_path: PathBuf,
+    // Resources loaded inside `record_loads`, with the modification time of
+    // the file that was loaded, `None` when it could not be opened
+    recorded_loads: RefCell<Option<Vec<LoadedResource>>>,
 }
 
 impl Resources {
@@ -36,6 +41,7 @@ impl Resources {
 
         Ok(Resources {
             root_path: exe_path.join(rel_path),
+            recorded_loads: RefCell::new(None),
         })
     }
 
@@ -43,8 +49,21 @@ impl Resources {
         Resources::from_relative_exe_path(Path::new(""))
     }
 
+    /// Resources read straight from directory `root_path`
+    pub fn from_path(root_path: &Path) -> Resources {
+        Resources {
+            root_path: root_path.into(),
+            recorded_loads: RefCell::new(None),
+        }
+    }
+
+    /// Whether `resource_name` names an existing file
+    pub fn exists(&self, resource_name: &str) -> bool {
+        resource_name_to_path(&self.root_path, resource_name).is_file()
+    }
+
     pub fn load_cstring(&self, resource_name: &str) -> Result<ffi::CString, Error> {
-        let mut file = fs::File::open(resource_name_to_path(&self.root_path, resource_name))?;
+        let mut file = self.open(resource_name)?;
 
         // allocate buffer of the same size as the file
         let mut buffer: Vec<u8> = Vec::with_capacity(file.metadata()?.len() as usize + 1);
@@ -57,8 +76,73 @@ impl Resources {
 
         Ok(unsafe { ffi::CString::from_vec_unchecked(buffer) })
     }
+
+    pub fn load_bytes(&self, resource_name: &str) -> Result<Vec<u8>, Error> {
+        let mut file = self.open(resource_name)?;
+
+        let mut buffer: Vec<u8> = Vec::with_capacity(file.metadata()?.len() as usize);
+        file.read_to_end(&mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    fn open(&self, resource_name: &str) -> Result<fs::File, Error> {
+        let file = fs::File::open(resource_name_to_path(&self.root_path, resource_name));
+
+        // Remember which version of the file was loaded, so callers of
+        // `record_loads` can tell when it changes
+        if let Some(loads) = self.recorded_loads.borrow_mut().as_mut() {
+            let modified = file
+                .as_ref()
+                .ok()
+                .and_then(|file| file.metadata().and_then(|metadata| metadata.modified()).ok());
+            loads.push((resource_name.to_owned(), modified));
+        }
+
+        Ok(file?)
+    }
+
+    /// Writes `bytes` to resource `resource_name`, creating missing directories
+    pub fn save_bytes(&self, resource_name: &str, bytes: &[u8]) -> Result<(), Error> {
+        let path = resource_name_to_path(&self.root_path, resource_name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(fs::write(path, bytes)?)
+    }
+
+    /// Runs `f` and returns, next to its result, every resource `f` tried to
+    /// load with the modification time of the file it got, `None` for files
+    /// that could not be opened. Pass them to `is_modified` later.
+    pub fn record_loads<R>(&self, f: impl FnOnce() -> R) -> (R, Vec<LoadedResource>) {
+        let outer = self.recorded_loads.replace(Some(Vec::new()));
+        let result = f();
+        let loads = self.recorded_loads.replace(outer).unwrap_or_default();
+
+        // Loads of nested recordings belong to the outer one as well
+        if let Some(outer) = self.recorded_loads.borrow_mut().as_mut() {
+            outer.extend(loads.iter().cloned());
+        }
+
+        (result, loads)
+    }
+
+    /// Whether the file of a resource recorded by `record_loads` changed since.
+    /// False while the file is missing (editors often delete and recreate
+    /// files on save), true once a file that could not be opened appears.
+    pub fn is_modified(&self, loaded: &LoadedResource) -> bool {
+        let (resource_name, load_time) = loaded;
+
+        fs::metadata(resource_name_to_path(&self.root_path, resource_name))
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| Some(modified) != *load_time)
+            .unwrap_or(false)
+    }
 }
 
+/// Resource name and modification time of the file when it was loaded
+pub type LoadedResource = (String, Option<SystemTime>);
+
 /// Deconstructs the location path and re-adds it to the root_dir
 /// using std's path::join() so separators work on all platforms
 fn resource_name_to_path(root_dir: &Path, location: &str) -> PathBuf {
@@ -70,3 +154,111 @@ fn resource_name_to_path(root_dir: &Path, location: &str) -> PathBuf {
 
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Empty directory for one test, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = ::std::env::temp_dir()
+                .join(format!("gladius-resources-{}-{}", ::std::process::id(), name));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Moves the modification time of `path` one minute ahead
+    fn touch(path: &Path) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        let modified = file.metadata().unwrap().modified().unwrap();
+        file.set_modified(modified + Duration::from_secs(60)).unwrap();
+    }
+
+    #[test]
+    fn records_loaded_resources() {
+        let dir = TempDir::new("records");
+        fs::write(dir.0.join("a.glsl"), "a").unwrap();
+        let res = Resources::from_path(&dir.0);
+
+        let (source, loads) = res.record_loads(|| res.load_cstring("a.glsl").unwrap());
+
+        assert_eq!(source.to_str().unwrap(), "a");
+        assert_eq!(loads.len(), 1);
+        assert_eq!(loads[0].0, "a.glsl");
+        assert!(loads[0].1.is_some());
+        assert!(!res.is_modified(&loads[0]));
+
+        touch(&dir.0.join("a.glsl"));
+        assert!(res.is_modified(&loads[0]));
+    }
+
+    #[test]
+    fn loads_outside_a_recording_are_not_recorded() {
+        let dir = TempDir::new("outside");
+        fs::write(dir.0.join("a.glsl"), "a").unwrap();
+        let res = Resources::from_path(&dir.0);
+
+        res.load_bytes("a.glsl").unwrap();
+        let (_, loads) = res.record_loads(|| ());
+
+        assert!(loads.is_empty());
+    }
+
+    #[test]
+    fn missing_files_count_as_modified_once_they_appear() {
+        let dir = TempDir::new("missing");
+        let res = Resources::from_path(&dir.0);
+
+        let (result, loads) = res.record_loads(|| res.load_bytes("a.glsl"));
+
+        assert!(result.is_err());
+        assert_eq!(loads, vec![("a.glsl".to_owned(), None)]);
+        assert!(!res.is_modified(&loads[0]));
+
+        fs::write(dir.0.join("a.glsl"), "a").unwrap();
+        assert!(res.is_modified(&loads[0]));
+    }
+
+    #[test]
+    fn deleted_files_are_not_modified() {
+        let dir = TempDir::new("deleted");
+        fs::write(dir.0.join("a.glsl"), "a").unwrap();
+        let res = Resources::from_path(&dir.0);
+
+        let (_, loads) = res.record_loads(|| res.load_bytes("a.glsl"));
+        fs::remove_file(dir.0.join("a.glsl")).unwrap();
+
+        assert!(!res.is_modified(&loads[0]));
+    }
+
+    #[test]
+    fn nested_recordings_also_reach_the_outer_one() {
+        let dir = TempDir::new("nested");
+        fs::write(dir.0.join("a.glsl"), "a").unwrap();
+        fs::write(dir.0.join("b.glsl"), "b").unwrap();
+        let res = Resources::from_path(&dir.0);
+
+        let ((_, inner), outer) = res.record_loads(|| {
+            res.load_bytes("a.glsl").unwrap();
+            res.record_loads(|| res.load_bytes("b.glsl").unwrap())
+        });
+
+        let names = |loads: &[LoadedResource]| {
+            loads.iter().map(|(name, _)| name.clone()).collect::<Vec<String>>()
+        };
+        assert_eq!(names(&inner), vec!["b.glsl"]);
+        assert_eq!(names(&outer), vec!["a.glsl", "b.glsl"]);
+    }
+}
@@ -6,13 +6,15 @@ use anyhow::{Error, Result};
 #[derive(VertexAttribPointers, Copy, Clone, Debug)]
 #[repr(C, packed)]
 struct Vertex {
+    #[location = 0]
     pos: data::f32_f32_f32,
+    #[location = 1]
     clr: data::f32_f32_f32,
 }
 
 pub struct Triangle {
-    program: render_gl::Program,
-    _vbo: buffer::ArrayBuffer, // _ to disable warning about not used vbo
+    program: render_gl::ReloadableProgram,
+    vbo: buffer::VertexBuffer<Vertex>,
     vao: buffer::VertexArray,
 }
 
@@ -22,7 +24,7 @@ impl Triangle {
         // data for a triangle and wrap this data in a
         // Triangle struct or return an Error
 
-        let program = render_gl::Program::from_res(&gl, &res, "shaders/triangle")?;
+        let program = render_gl::ReloadableProgram::from_res(&gl, &res, "shaders/triangle")?;
 
         // Set up VBO (Vertex Buffer Object)
 
@@ -36,7 +38,7 @@ impl Triangle {
 
         // Create VBO
 
-        let vbo = buffer::ArrayBuffer::new(&gl);
+        let mut vbo = buffer::VertexBuffer::new(&gl);
         vbo.bind();
         vbo.static_draw_data(&vertices);
         vbo.unbind();
@@ -44,30 +46,29 @@ impl Triangle {
 
         // Setup VAO (Vertex Array Object)
         let vao = buffer::VertexArray::new(&gl);
-        vao.bind();
-        vbo.bind();
-        Vertex::vertex_attrib_pointers(&gl);
-        vbo.unbind();
-        vao.unbind();
+        vao.set_vertex_buffer(&vbo);
 
         Ok(Triangle {
             program,
-            _vbo: vbo,
+            vbo,
             vao,
         })
     }
 
-    pub fn render(&self, gl: &gl::Gl) {
-        // function that renders the triangle based on loaded data
-        self.program.set_used();
-        self.vao.bind();
+    /// Picks up edits to the triangle shaders, keeping the
+    /// current ones when the edited sources fail to build
+    pub fn reload_shaders(&mut self, res: &Resources) -> Result<bool, render_gl::Error> {
+        self.program.poll(res)
+    }
 
-        unsafe {
-            gl.DrawArrays(
-                gl::TRIANGLES, // mode
-                0,             // Starting index in the enabled arrays
-                3,             // Number of indices to be rendered
-            );
-        }
+    /// Warnings the driver reported for the triangle shaders
+    pub fn shader_diagnostics(&self) -> &[render_gl::diagnostics::Diagnostic] {
+        self.program.program().diagnostics()
+    }
+
+    pub fn render(&self) {
+        // function that renders the triangle based on loaded data
+        self.program.program().set_used();
+        self.vao.draw_arrays(gl::TRIANGLES, &self.vbo);
     }
 }